@@ -1,14 +1,11 @@
-struct FragmentInput {
-    screen_size: vec2<u32>,
-    frame: u32,
-    seed: u32
-};
-@group(0) @binding(0) var<uniform> fragment_input: FragmentInput;
+#import noise
+#import color
 
 const SCROLL_SPEED: f32 = 0.001;
 const NOISE_SCALE: f32 = 0.02;
-const COLOR_1: vec3f = vec3(22.0, 22.0, 22.0);
-const COLOR_2: vec3f = vec3(99.0, 56.0, 143.0);
+// sRGB, 0 to 255
+const COLOR_1: vec3f = vec3(4.9, 4.9, 4.9);
+const COLOR_2: vec3f = vec3(17.84, 11.38, 22.95);
 
 @fragment
 fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
@@ -23,150 +20,19 @@ fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
         let scaled_x = pos.x * noise_scale;
         let scaled_y = pos.y * noise_scale;
 
-        let dist_x = perlinNoise2(vec2(scaled_x + 5.125, scaled_y + 12.12));
-        let dist_y = perlinNoise2(vec2(scaled_x + 5.236, scaled_y + 15.2));
+        let dist_x = gpuburden_perlin_noise2(vec2(scaled_x + 5.125, scaled_y + 12.12));
+        let dist_y = gpuburden_perlin_noise2(vec2(scaled_x + 5.236, scaled_y + 15.2));
 
         let sample_x = (scaled_x + dist_x) + (speed_adjusted_frame * 4);
         let sample_y = (scaled_y + dist_y) + (speed_adjusted_frame * 4);
         let sample_z = 1.0;
 
-        value += perlinNoise3(vec3(sample_x, sample_y, sample_z)) * freq;
+        value += gpuburden_perlin_noise3(vec3(sample_x, sample_y, sample_z)) * freq;
         freq *= 0.75;
         noise_scale += 0.05;
     }
 
-    var color = vec3<f32>(
-        lerp(COLOR_1.x, COLOR_2.x, value),
-        lerp(COLOR_1.y, COLOR_2.y, value),
-        lerp(COLOR_1.z, COLOR_2.z, value),
-    ) * 4; // don't ask about the * 4, it just makes it look better even tho it should break it
-    color = srgb_to_rgb3(color);
-    return vec4<f32>(color / 255.0, 1.0);
+    // blended in linear space
+    let color = mix(gpuburden_srgb_to_linear(COLOR_1 / 255.0), gpuburden_srgb_to_linear(COLOR_2 / 255.0), value);
+    return vec4<f32>(color, 1.0);
 }
-
-fn lerp(a: f32, b: f32, t: f32) -> f32 {
-    return a + (b - a) * t;
-}
-fn srgb_to_rgb(srgb_color: f32) -> f32 {
-    return ((srgb_color / 255 + 0.055) / 1.055);
-}
-fn srgb_to_rgb3(srgb_color: vec3<f32>) -> vec3<f32> {
-    return vec3(
-        srgb_to_rgb(srgb_color.x),
-        srgb_to_rgb(srgb_color.y),
-        srgb_to_rgb(srgb_color.z)
-    );
-}
-
-
-
-// Perlin Noise 
-// MIT License. © Stefan Gustavson, Munrocket
-fn permute4(x: vec4f) -> vec4f { return ((x * 34. + 1.) * x) % vec4f(289.); }
-fn taylorInvSqrt4(r: vec4f) -> vec4f { return 1.79284291400159 - 0.85373472095314 * r; }
-fn fade2(t: vec2f) -> vec2f { return t * t * t * (t * (t * 6. - 15.) + 10.); }
-fn fade3(t: vec3f) -> vec3f { return t * t * t * (t * (t * 6. - 15.) + 10.); }
-
-fn perlinNoise2(P: vec2f) -> f32 {
-    var Pi: vec4f = floor(P.xyxy) + vec4f(0., 0., 1., 1.);
-    let Pf = fract(P.xyxy) - vec4f(0., 0., 1., 1.);
-    Pi = Pi % vec4f(289.); // To avoid truncation effects in permutation
-    let ix = Pi.xzxz;
-    let iy = Pi.yyww;
-    let fx = Pf.xzxz;
-    let fy = Pf.yyww;
-    let i = permute4(permute4(ix) + iy);
-    var gx: vec4f = 2. * fract(i * 0.0243902439) - 1.; // 1/41 = 0.024...
-    let gy = abs(gx) - 0.5;
-    let tx = floor(gx + 0.5);
-    gx = gx - tx;
-    var g00: vec2f = vec2f(gx.x, gy.x);
-    var g10: vec2f = vec2f(gx.y, gy.y);
-    var g01: vec2f = vec2f(gx.z, gy.z);
-    var g11: vec2f = vec2f(gx.w, gy.w);
-    let norm = 1.79284291400159 - 0.85373472095314 *
-        vec4f(dot(g00, g00), dot(g01, g01), dot(g10, g10), dot(g11, g11));
-    g00 = g00 * norm.x;
-    g01 = g01 * norm.y;
-    g10 = g10 * norm.z;
-    g11 = g11 * norm.w;
-    let n00 = dot(g00, vec2f(fx.x, fy.x));
-    let n10 = dot(g10, vec2f(fx.y, fy.y));
-    let n01 = dot(g01, vec2f(fx.z, fy.z));
-    let n11 = dot(g11, vec2f(fx.w, fy.w));
-    let fade_xy = fade2(Pf.xy);
-    let n_x = mix(vec2f(n00, n01), vec2f(n10, n11), vec2f(fade_xy.x));
-    let n_xy = mix(n_x.x, n_x.y, fade_xy.y);
-    return 2.3 * n_xy;
-}
-fn perlinNoise3(P: vec3f) -> f32 {
-    var Pi0 : vec3f = floor(P); // Integer part for indexing
-    var Pi1 : vec3f = Pi0 + vec3f(1.); // Integer part + 1
-    Pi0 = Pi0 % vec3f(289.);
-    Pi1 = Pi1 % vec3f(289.);
-    let Pf0 = fract(P); // Fractional part for interpolation
-    let Pf1 = Pf0 - vec3f(1.); // Fractional part - 1.
-    let ix = vec4f(Pi0.x, Pi1.x, Pi0.x, Pi1.x);
-    let iy = vec4f(Pi0.yy, Pi1.yy);
-    let iz0 = Pi0.zzzz;
-    let iz1 = Pi1.zzzz;
-
-    let ixy = permute4(permute4(ix) + iy);
-    let ixy0 = permute4(ixy + iz0);
-    let ixy1 = permute4(ixy + iz1);
-
-    var gx0: vec4f = ixy0 / 7.;
-    var gy0: vec4f = fract(floor(gx0) / 7.) - 0.5;
-    gx0 = fract(gx0);
-    var gz0: vec4f = vec4f(0.5) - abs(gx0) - abs(gy0);
-    var sz0: vec4f = step(gz0, vec4f(0.));
-    gx0 = gx0 + sz0 * (step(vec4f(0.), gx0) - 0.5);
-    gy0 = gy0 + sz0 * (step(vec4f(0.), gy0) - 0.5);
-
-    var gx1: vec4f = ixy1 / 7.;
-    var gy1: vec4f = fract(floor(gx1) / 7.) - 0.5;
-    gx1 = fract(gx1);
-    var gz1: vec4f = vec4f(0.5) - abs(gx1) - abs(gy1);
-    var sz1: vec4f = step(gz1, vec4f(0.));
-    gx1 = gx1 - sz1 * (step(vec4f(0.), gx1) - 0.5);
-    gy1 = gy1 - sz1 * (step(vec4f(0.), gy1) - 0.5);
-
-    var g000: vec3f = vec3f(gx0.x, gy0.x, gz0.x);
-    var g100: vec3f = vec3f(gx0.y, gy0.y, gz0.y);
-    var g010: vec3f = vec3f(gx0.z, gy0.z, gz0.z);
-    var g110: vec3f = vec3f(gx0.w, gy0.w, gz0.w);
-    var g001: vec3f = vec3f(gx1.x, gy1.x, gz1.x);
-    var g101: vec3f = vec3f(gx1.y, gy1.y, gz1.y);
-    var g011: vec3f = vec3f(gx1.z, gy1.z, gz1.z);
-    var g111: vec3f = vec3f(gx1.w, gy1.w, gz1.w);
-
-    let norm0 = taylorInvSqrt4(
-        vec4f(dot(g000, g000), dot(g010, g010), dot(g100, g100), dot(g110, g110)));
-    g000 = g000 * norm0.x;
-    g010 = g010 * norm0.y;
-    g100 = g100 * norm0.z;
-    g110 = g110 * norm0.w;
-    let norm1 = taylorInvSqrt4(
-        vec4f(dot(g001, g001), dot(g011, g011), dot(g101, g101), dot(g111, g111)));
-    g001 = g001 * norm1.x;
-    g011 = g011 * norm1.y;
-    g101 = g101 * norm1.z;
-    g111 = g111 * norm1.w;
-
-    let n000 = dot(g000, Pf0);
-    let n100 = dot(g100, vec3f(Pf1.x, Pf0.yz));
-    let n010 = dot(g010, vec3f(Pf0.x, Pf1.y, Pf0.z));
-    let n110 = dot(g110, vec3f(Pf1.xy, Pf0.z));
-    let n001 = dot(g001, vec3f(Pf0.xy, Pf1.z));
-    let n101 = dot(g101, vec3f(Pf1.x, Pf0.y, Pf1.z));
-    let n011 = dot(g011, vec3f(Pf0.x, Pf1.yz));
-    let n111 = dot(g111, Pf1);
-
-    var fade_xyz: vec3f = fade3(Pf0);
-    let temp = vec4f(f32(fade_xyz.z)); // simplify after chrome bug fix
-    let n_z = mix(vec4f(n000, n100, n010, n110), vec4f(n001, n101, n011, n111), temp);
-    let n_yz = mix(n_z.xy, n_z.zw, vec2f(f32(fade_xyz.y))); // simplify after chrome bug fix
-    let n_xyz = mix(n_yz.x, n_yz.y, fade_xyz.x);
-    return 2.2 * n_xyz;
-}
-
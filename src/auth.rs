@@ -0,0 +1,176 @@
+use std::{ffi::{c_char, c_int, c_void, CStr, CString}, mem::MaybeUninit, ptr};
+
+use zeroize::Zeroize;
+
+use crate::sys;
+
+// Checks the password typed into `gpuburden lock`.
+pub trait Authenticator {
+    fn authenticate(&mut self, password: &str) -> Result<(), String>;
+}
+
+// Checks the password of the user running gpuburden through PAM, using /etc/pam.d/<service>.
+pub struct Pam {
+    service: CString,
+    user: CString
+}
+
+impl Pam {
+    pub fn new(service: &str) -> Result<Self, String> {
+        Ok(Self {
+            service: CString::new(service).map_err(|e| e.to_string())?,
+            user: current_user()?
+        })
+    }
+
+    // The password has to stay put while PAM has a pointer to it.
+    fn converse_with(&self, password: &CStr) -> Result<(), String> {
+        let conversation = PamConv { conv: converse, appdata_ptr: password.as_ptr() as *mut c_void };
+        let mut handle = ptr::null_mut();
+        unsafe {
+            let result = pam_start(self.service.as_ptr(), self.user.as_ptr(), &conversation, &mut handle);
+            if result != PAM_SUCCESS {
+                return Err(format!("pam_start failed ({result})"));
+            }
+            let mut result = pam_authenticate(handle, 0);
+            if result == PAM_SUCCESS {
+                // expired accounts and the like
+                result = pam_acct_mgmt(handle, 0);
+            }
+            let message = CStr::from_ptr(pam_strerror(handle, result)).to_string_lossy().into_owned();
+            pam_end(handle, result);
+            match result {
+                PAM_SUCCESS => Ok(()),
+                _ => Err(message)
+            }
+        }
+    }
+}
+
+// The user gpuburden is running as, from the password database. $USER can be set to anything by
+// whatever starts the locker, and often isn't set at all under swayidle or systemd.
+fn current_user() -> Result<CString, String> {
+    let uid = sys::uid();
+    let mut buffer: Vec<c_char> = vec![0; 1024];
+    loop {
+        let mut entry = MaybeUninit::<Passwd>::uninit();
+        let mut result = ptr::null_mut();
+        let error = unsafe { getpwuid_r(uid, entry.as_mut_ptr(), buffer.as_mut_ptr(), buffer.len(), &mut result) };
+        if error == ERANGE {
+            buffer.resize(buffer.len() * 2, 0);
+            continue;
+        }
+        if result.is_null() {
+            return Err(format!("couldn't work out who you are, uid {uid} has no password entry"));
+        }
+        return Ok(unsafe { CStr::from_ptr((*result).pw_name) }.to_owned());
+    }
+}
+
+impl Authenticator for Pam {
+    fn authenticate(&mut self, password: &str) -> Result<(), String> {
+        let mut password = CString::new(password).map_err(|_| "the password can't contain a null character".to_string())?;
+        let result = self.converse_with(&password);
+        // PAM frees its own copies, this one is ours to clear
+        password.zeroize();
+        result
+    }
+}
+
+// Answers every prompt PAM has with the password, and prints anything it wants to tell the user.
+extern "C" fn converse(num_msg: c_int, msg: *mut *const PamMessage, resp: *mut *mut PamResponse, appdata_ptr: *mut c_void) -> c_int {
+    unsafe {
+        // PAM frees these itself, so they have to come from malloc
+        let responses = calloc(num_msg as usize, size_of::<PamResponse>()) as *mut PamResponse;
+        if responses.is_null() {
+            return PAM_BUF_ERR;
+        }
+        for index in 0..num_msg as usize {
+            let message = &**msg.add(index);
+            match message.msg_style {
+                PAM_PROMPT_ECHO_OFF | PAM_PROMPT_ECHO_ON => (*responses.add(index)).resp = strdup(appdata_ptr as *const c_char),
+                _ => println!("{}", CStr::from_ptr(message.msg).to_string_lossy())
+            }
+        }
+        *resp = responses;
+    }
+    PAM_SUCCESS
+}
+
+const PAM_SUCCESS: c_int = 0;
+const PAM_BUF_ERR: c_int = 5;
+const PAM_PROMPT_ECHO_OFF: c_int = 1;
+const PAM_PROMPT_ECHO_ON: c_int = 2;
+
+#[repr(C)]
+struct PamMessage {
+    msg_style: c_int,
+    msg: *const c_char
+}
+
+#[repr(C)]
+struct PamResponse {
+    resp: *mut c_char,
+    resp_retcode: c_int
+}
+
+#[repr(C)]
+struct PamConv {
+    conv: extern "C" fn(c_int, *mut *const PamMessage, *mut *mut PamResponse, *mut c_void) -> c_int,
+    appdata_ptr: *mut c_void
+}
+
+#[link(name = "pam")]
+unsafe extern "C" {
+    fn pam_start(service_name: *const c_char, user: *const c_char, pam_conversation: *const PamConv, pamh: *mut *mut c_void) -> c_int;
+    fn pam_authenticate(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_acct_mgmt(pamh: *mut c_void, flags: c_int) -> c_int;
+    fn pam_strerror(pamh: *mut c_void, errnum: c_int) -> *const c_char;
+    fn pam_end(pamh: *mut c_void, pam_status: c_int) -> c_int;
+}
+
+const ERANGE: c_int = 34;
+
+#[repr(C)]
+struct Passwd {
+    pw_name: *mut c_char,
+    pw_passwd: *mut c_char,
+    pw_uid: u32,
+    pw_gid: u32,
+    pw_gecos: *mut c_char,
+    pw_dir: *mut c_char,
+    pw_shell: *mut c_char
+}
+
+unsafe extern "C" {
+    fn calloc(count: usize, size: usize) -> *mut c_void;
+    fn strdup(s: *const c_char) -> *mut c_char;
+    fn getpwuid_r(uid: u32, pwd: *mut Passwd, buf: *mut c_char, buflen: usize, result: *mut *mut Passwd) -> c_int;
+}
+
+// Accepts one password, so the lock screen can be tested without touching the real PAM setup.
+#[cfg(test)]
+pub struct StubAuthenticator {
+    pub password: String,
+    pub attempts: u32
+}
+
+#[cfg(test)]
+impl Authenticator for StubAuthenticator {
+    fn authenticate(&mut self, password: &str) -> Result<(), String> {
+        self.attempts += 1;
+        match password == self.password {
+            true => Ok(()),
+            false => Err("Authentication failure".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn finds_the_current_user() {
+        let user = super::current_user().expect("the test user should have a password entry");
+        assert!(!user.as_bytes().is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use crate::{cli::BenchArgs, headless::{exit_with, shader_config}, renderer::Renderer};
+
+pub fn bench(args: &BenchArgs) {
+    let (width, height) = args.size;
+    if args.warmup.checked_add(args.frames).is_none() {
+        exit_with(&format!("frame numbers go past {} with --warmup {} --frames {}", u32::MAX, args.warmup, args.frames));
+    }
+    let config = shader_config(&args.shader, args.seed);
+    let mut renderer = match Renderer::headless(width, height, &config) {
+        Ok(r) => r,
+        Err(e) => exit_with(&e)
+    };
+    let mut gpu_timing = renderer.enable_gpu_timing();
+    let info = renderer.adapter_info();
+    println!("benchmarking {} at {width}x{height} on {} ({:?}), {} frames", args.shader, info.name, info.backend, args.frames);
+    if gpu_timing {
+        println!("timing with GPU timestamp queries");
+    } else {
+        println!("timestamp queries aren't supported here, timing with CPU wall time instead (includes submission overhead)");
+    }
+
+    for frame in 0..args.warmup {
+        renderer.render_offscreen(frame);
+        renderer.take_gpu_time(true);
+        renderer.wait_idle();
+    }
+
+    // GPU and CPU times aren't comparable, so if the GPU stops answering partway through the whole
+    // run starts again on the CPU clock
+    let mut times = loop {
+        match measure(&mut renderer, args, gpu_timing) {
+            Some(r) => break r,
+            None => {
+                println!("a GPU timestamp didn't come back, starting again with CPU wall time");
+                gpu_timing = false;
+                // stop writing timestamps as well, they'd be counted in the CPU times
+                renderer.disable_gpu_timing();
+            }
+        }
+    };
+    times.sort();
+
+    let average = times.iter().sum::<Duration>() / times.len() as u32;
+    println!("  average {}  ({:.0} fps)", format_time(average), 1.0 / average.as_secs_f64());
+    println!("  min     {}", format_time(times[0]));
+    println!("  p50     {}", format_time(percentile(&times, 0.50)));
+    println!("  p95     {}", format_time(percentile(&times, 0.95)));
+    println!("  p99     {}", format_time(percentile(&times, 0.99)));
+    println!("  max     {}", format_time(times[times.len() - 1]));
+}
+
+// None if GPU timing was asked for and a frame couldn't be timed.
+fn measure(renderer: &mut Renderer, args: &BenchArgs, gpu_timing: bool) -> Option<Vec<Duration>> {
+    let mut times = Vec::new();
+    // can't overflow since it was checked before starting
+    for frame in args.warmup..args.warmup + args.frames {
+        let start = Instant::now();
+        renderer.render_offscreen(frame);
+        let time = match gpu_timing {
+            true => renderer.take_gpu_time(true)?,
+            false => {
+                renderer.wait_idle();
+                start.elapsed()
+            }
+        };
+        times.push(time);
+    }
+    Some(times)
+}
+
+fn percentile(sorted: &[Duration], fraction: f64) -> Duration {
+    let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+fn format_time(time: Duration) -> String {
+    format!("{:>8.3} ms", time.as_secs_f64() * 1000.0)
+}
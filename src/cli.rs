@@ -0,0 +1,164 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    /// Config file to use instead of ~/.config/gpuburden/gpuburden.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Shader to run on every output, overriding the config
+    #[arg(long, value_name = "PATH")]
+    pub shader: Option<String>,
+    /// Only draw on this output, can be given more than once
+    #[arg(long = "output", value_name = "NAME")]
+    pub outputs: Vec<String>,
+    /// Print more about what gpuburden is doing
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Render a single frame of a shader to a PNG without connecting to a compositor
+    Render(RenderArgs),
+    /// Render a run of frames to a PNG sequence, an animated GIF/WebP, or an encoder command
+    Record(RecordArgs),
+    /// Render a shader offscreen for a number of frames and report how long the GPU takes per frame
+    Bench(BenchArgs),
+    /// Print frame rate, frame times and dropped frames for each output of the running daemon
+    Stats,
+    /// Lock the screen, drawing the configured shaders behind a password prompt
+    #[cfg(feature = "lock")]
+    Lock(LockArgs),
+    /// Open a shader in a window that reloads it on save, with pause, frame stepping and a time scrubber
+    Preview(PreviewArgs)
+}
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Shader to render, or "default" for the built-in one
+    #[arg(long)]
+    pub shader: String,
+    /// Image size as WIDTHxHEIGHT
+    #[arg(long, default_value = "1920x1080", value_parser = parse_size)]
+    pub size: (u32, u32),
+    /// Frame number passed to the shader
+    #[arg(long, default_value_t = 0)]
+    pub frame: u32,
+    /// Seed passed to the shader, random if not set
+    #[arg(long)]
+    pub seed: Option<u32>,
+    /// Where to write the PNG
+    #[arg(short, long)]
+    pub output: PathBuf
+}
+
+#[derive(Args)]
+pub struct RecordArgs {
+    /// Shader to record, or "default" for the built-in one
+    #[arg(long)]
+    pub shader: String,
+    /// Image size as WIDTHxHEIGHT
+    #[arg(long, default_value = "640x360", value_parser = parse_size)]
+    pub size: (u32, u32),
+    /// Number of frames to record
+    #[arg(long, default_value_t = 120)]
+    pub frames: u32,
+    /// Frame number of the first recorded frame
+    #[arg(long, default_value_t = 0)]
+    pub start: u32,
+    /// How far the frame number advances between recorded frames
+    #[arg(long, default_value_t = 1)]
+    pub step: u32,
+    /// Playback speed for GIF/WebP output and the {fps} placeholder of --pipe. Shader time is always frame / 60, so use --step to change how fast it moves
+    #[arg(long, default_value_t = 60.0, value_parser = parse_fps)]
+    pub fps: f32,
+    /// Seed passed to the shader, random if not set
+    #[arg(long)]
+    pub seed: Option<u32>,
+    /// Output file, or a directory for PNG sequences
+    #[arg(short, long, required_unless_present = "pipe")]
+    pub output: Option<PathBuf>,
+    /// Output format, guessed from the output's extension if not set
+    #[arg(long, value_enum)]
+    pub format: Option<RecordFormat>,
+    /// Shell command to pipe raw RGBA frames into, {width}, {height} and {fps} are replaced
+    #[arg(long, conflicts_with_all = ["output", "format"])]
+    pub pipe: Option<String>
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// Shader to benchmark, or "default" for the built-in one
+    pub shader: String,
+    /// Render size as WIDTHxHEIGHT
+    #[arg(long, default_value = "1920x1080", value_parser = parse_size)]
+    pub size: (u32, u32),
+    /// Number of frames to measure
+    #[arg(long, default_value_t = 300, value_parser = clap::value_parser!(u32).range(1..))]
+    pub frames: u32,
+    /// Frames rendered before measuring starts
+    #[arg(long, default_value_t = 30)]
+    pub warmup: u32,
+    /// Seed passed to the shader, random if not set
+    #[arg(long)]
+    pub seed: Option<u32>
+}
+
+#[cfg(feature = "lock")]
+#[derive(Args)]
+pub struct LockArgs {
+    /// Config file to use instead of ~/.config/gpuburden/gpuburden.toml
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Shader to run on every output, overriding the config
+    #[arg(long, value_name = "PATH")]
+    pub shader: Option<String>,
+    /// PAM service the password is checked with, from /etc/pam.d
+    #[arg(long, default_value = "gpuburden")]
+    pub pam_service: String
+}
+
+#[derive(Args)]
+pub struct PreviewArgs {
+    /// Shader to preview, or "default" for the built-in one
+    pub shader: String,
+    /// Starting window size as WIDTHxHEIGHT
+    #[arg(long, default_value = "1280x720", value_parser = parse_size)]
+    pub size: (u32, u32),
+    /// Seconds covered by the scrubber before playback loops
+    #[arg(long, default_value_t = 10.0)]
+    pub duration: f32,
+    /// Seed passed to the shader, random if not set
+    #[arg(long)]
+    pub seed: Option<u32>
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum RecordFormat {
+    Png,
+    Gif,
+    Webp
+}
+
+fn parse_fps(fps: &str) -> Result<f32, String> {
+    let fps: f32 = fps.trim().parse().map_err(|e| format!("bad fps: {e}"))?;
+    if !fps.is_finite() || fps <= 0.0 {
+        return Err("fps must be a number above 0".to_string());
+    }
+    Ok(fps)
+}
+fn parse_size(size: &str) -> Result<(u32, u32), String> {
+    let (width, height) = size.split_once('x').ok_or("size should look like 1920x1080")?;
+    let width: u32 = width.trim().parse().map_err(|e| format!("bad width: {e}"))?;
+    let height: u32 = height.trim().parse().map_err(|e| format!("bad height: {e}"))?;
+    if width == 0 || height == 0 {
+        return Err("width and height must be above 0".to_string());
+    }
+    Ok((width, height))
+}
@@ -1,92 +1,345 @@
-use std::{env, path::{Path, PathBuf}};
+use std::{env, fs, path::{Path, PathBuf}, process, time::Duration};
 
-use config::{Config, File};
+use rand::Rng;
 use serde::Deserialize;
 
-#[derive(Clone, Debug, Deserialize)]
+use crate::validation::{self, ConfigCheck, Problem};
+
+#[derive(Clone, Debug, Default, Deserialize)]
 pub struct MonitorConfig {
+    // connector name or a pattern like "HDMI-*", any output if not set
+    #[serde(default = "any_output")]
     pub name: String,
-    pub shader: String
+    // when set these have to match the output as well, so the entry follows the physical monitor
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub description: Option<String>,
+    pub physical_size: Option<[i32; 2]>,
+
+    pub shader: String,
+    pub seed: Option<u32>,
+    // render at the logical size and let the compositor scale it up, to save GPU time on HiDPI screens
+    #[serde(default)]
+    pub render_logical_size: bool,
+    // pass the pointer position and clicks to the shader
+    #[serde(default)]
+    pub pointer: bool,
+
+    // where the surface goes, by default it's a background covering the whole output
+    #[serde(default)]
+    pub layer: LayerConfig,
+    // edges the surface is attached to, all of them if not set
+    pub anchor: Option<Vec<Edge>>,
+    // logical pixels, 0 to stretch between the anchored edges
+    pub size: Option<[u32; 2]>,
+    // top, right, bottom, left
+    #[serde(default)]
+    pub margin: [i32; 4],
+    // space other surfaces should keep clear of, -1 (under any panels) if not set
+    pub exclusive_zone: Option<i32>,
+    // blend the shader's alpha over whatever is below and let clicks through, for overlays
+    #[serde(default)]
+    pub transparent: bool,
+    // draw shader errors on the output instead of only printing them, the top level setting if not set
+    pub show_errors: Option<bool>,
+
+    #[serde(skip)]
+    pub include_paths: Vec<PathBuf>
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct Configuration {
-    monitors: Vec<MonitorConfig>
+// Outputs that share one canvas, so a shader can flow across all of them.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SpanConfig {
+    // names or patterns
+    pub outputs: Vec<String>,
+    // shared by every output in the span so they draw the same thing, random if not set
+    pub seed: Option<u32>
 }
-impl Configuration {
-    pub fn load() -> Option<Configuration> {
-        let config_path = Configuration::locate_config_path();
-        if config_path.is_none() {
-            println!("Config path not found.");
-            println!("Backing up to default.");
-            return None;
-        }
-        let config_path = config_path.unwrap();
 
-        let config_file = Configuration::locate_config(&config_path);
-        if config_file.is_none() {
-            println!("Config file not found.");
-            println!("Backing up to default.");
-            return None;
-        }
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerConfig {
+    #[default]
+    Background,
+    Bottom,
+    Top,
+    Overlay
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right
+}
+
+fn any_output() -> String {
+    "*".to_string()
+}
+
+// What an output can be matched on, as the compositor describes it.
+#[derive(Default)]
+pub struct OutputIdentity {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    pub description: String,
+    pub physical_size: (i32, i32) // in millimeters
+}
+
+impl MonitorConfig {
+    pub fn anchored(&self, edge: Edge) -> bool {
+        self.anchor.as_ref().is_none_or(|anchor| anchor.contains(&edge))
+    }
+
+    // The size to ask for, 0 lets the compositor stretch it between the edges. That only works when
+    // both edges are anchored, otherwise it falls back to the output's size.
+    pub fn layer_size(&self, output_size: (u32, u32)) -> (u32, u32) {
+        let [width, height] = self.size.unwrap_or_default();
+        let width = match width {
+            0 if !(self.anchored(Edge::Left) && self.anchored(Edge::Right)) => output_size.0,
+            width => width
+        };
+        let height = match height {
+            0 if !(self.anchored(Edge::Top) && self.anchored(Edge::Bottom)) => output_size.1,
+            height => height
+        };
+        (width, height)
+    }
 
-        let config_build = Config::builder()
-            .add_source(File::from(config_file.unwrap()))
-            .build();
-        if let Err(err) = config_build {
-            println!("Failed to build configuration from file: {err}");
-            println!("Backing up to default.");
+    // None if the entry doesn't apply to the output, otherwise how specific the match is. Entries
+    // matching on more of the monitor beat ones that don't, then it comes down to the name.
+    fn specificity(&self, output: &OutputIdentity) -> Option<(usize, usize)> {
+        let name = name_specificity(&self.name, &output.name)?;
+        let checks = [
+            self.make.as_ref().map(|make| glob_match(make, &output.make)),
+            self.model.as_ref().map(|model| glob_match(model, &output.model)),
+            // wl_output has no serial, but compositors put it in the description
+            self.serial.as_ref().map(|serial| output.description.contains(serial.as_str())),
+            self.description.as_ref().map(|description| glob_match(description, &output.description)),
+            self.physical_size.map(|[width, height]| (width, height) == output.physical_size)
+        ];
+        if checks.contains(&Some(false)) {
             return None;
         }
+        Some((checks.iter().flatten().count(), name))
+    }
+}
 
-        let config = config_build.unwrap().try_deserialize::<Configuration>();
-        if let Err(err) = config {
-            println!("Failed to deserialize configuration from file: {err}");
-            println!("Backing up to default.");
-            return None;
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Configuration {
+    monitors: Vec<MonitorConfig>,
+    #[serde(default)]
+    spans: Vec<SpanConfig>,
+    // seconds between printing the per-output stats, never if not set
+    #[serde(default)]
+    stats_log_interval: Option<u64>,
+    // extra folders to look for shaders in, after the config folder and before the shared ones
+    #[serde(default)]
+    shader_paths: Vec<String>,
+    // show_errors for every output
+    #[serde(default)]
+    show_errors: bool,
+
+    // false when no config file could be used, every output then gets the default shader
+    #[serde(skip)]
+    loaded: bool,
+    #[serde(skip)]
+    include_paths: Vec<PathBuf>,
+    // set from the command line
+    #[serde(skip)]
+    shader_override: Option<String>,
+    #[serde(skip)]
+    output_filter: Vec<String>
+}
+impl Configuration {
+    // path is the config file given with --config, otherwise gpuburden.toml in the config folder is used
+    pub fn load(path: Option<&Path>) -> Configuration {
+        match Configuration::load_file(path) {
+            Some(r) => r,
+            None => {
+                println!("Backing up to default.");
+                Configuration {
+                    include_paths: Configuration::include_paths(),
+                    ..Default::default()
+                }
+            }
         }
-        let mut config = config.unwrap();
+    }
+    fn load_file(path: Option<&Path>) -> Option<Configuration> {
+        let config_file = match path {
+            Some(path) => {
+                // canonicalized so a bare file name still has a folder for the shaders
+                match path.canonicalize() {
+                    Ok(r) => r,
+                    Err(e) => {
+                        println!("Config file {} couldn't be opened: {e}", path.display());
+                        return None;
+                    }
+                }
+            },
+            None => {
+                let config_dirs = Configuration::config_dirs();
+                let config_file = config_dirs.iter().find_map(|path| Configuration::locate_config(path));
+                if config_file.is_none() {
+                    let searched: Vec<String> = config_dirs.iter().map(|path| path.display().to_string()).collect();
+                    println!("Config file not found in {}.", searched.join(", "));
+                    return None;
+                }
+                config_file.unwrap()
+            }
+        };
+        // shaders are relative to the folder the config is in
+        let config_path = config_file.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        let text = match fs::read_to_string(&config_file) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("Failed to read {}: {e}", config_file.display());
+                return None;
+            }
+        };
+        let mut check = validation::check(&text);
+        let mut config = match toml::from_str::<Configuration>(&text) {
+            Ok(r) => r,
+            Err(e) => {
+                // syntax errors were already found by the check
+                if !check.problems.iter().any(|problem| problem.span == e.span()) {
+                    check.problems.push(Problem { span: e.span(), message: e.message().to_string() });
+                }
+                Configuration::report_problems(&config_file, &text, &check);
+                return None;
+            }
+        };
+        verbose!("loaded config from {}", config_file.display());
 
         // convert all shaders into their paths 
-        let config_path_string = config_path.to_str();
-        for monitor in &mut config.monitors {
-            monitor.shader = format!("{}/{}", config_path_string.unwrap(), monitor.shader);
+        let search_paths = Configuration::search_paths(&config_path, &config.shader_paths);
+        for (index, monitor) in config.monitors.iter_mut().enumerate() {
+            let span = check.shader_spans.get(index).cloned().flatten();
+            match resolve_shader(&monitor.shader, &search_paths, &env_var) {
+                Ok(r) => {
+                    if r != "default" && !Path::new(&r).exists() {
+                        check.problems.push(Problem { span, message: format!("shader {r} doesn't exist") });
+                    }
+                    println!("{} uses shader {r}", monitor.name);
+                    monitor.shader = r;
+                },
+                Err(e) => check.problems.push(Problem { span, message: format!("can't resolve the shader: {e}") })
+            }
+            monitor.include_paths = search_paths.clone();
         }
+        for span in &mut config.spans {
+            span.seed.get_or_insert_with(|| rand::rng().random_range(0..1000000));
+        }
+        config.loaded = true;
+        config.include_paths = search_paths;
+        Configuration::report_problems(&config_file, &text, &check);
 
         Some(config)
     }
-    pub fn monitor_config(&self, name: &str) -> Option<MonitorConfig> {
-        for monitor in &self.monitors {
-            if monitor.name != name {
-                continue;
-            }
-            return Some(monitor.clone());
-        }
 
-        None
+    // Prints everything wrong with the file, and exits if the config asked to be strict about it.
+    fn report_problems(config_file: &Path, text: &str, check: &ConfigCheck) {
+        if check.problems.is_empty() {
+            return;
+        }
+        println!("{}", validation::report(config_file, text, &check.problems));
+        if check.strict {
+            println!("Refusing to start since the config has strict = true.");
+            process::exit(1);
+        }
+        println!("Carrying on anyway, set strict = true in the config to refuse to start instead.");
     }
 
-    fn locate_config_path() -> Option<PathBuf> {
-        if let Ok(mut config_home) = env::var("XDG_CONFIG_HOME") {
-            config_home.push_str("/gpuburden");
-            let path = PathBuf::from(config_home);
-            if path.exists() {
-                return Some(path);
-            } 
+    // Layers the command line's --shader and --output on top of the file.
+    // The shader is relative to the current directory rather than the config.
+    pub fn apply_overrides(&mut self, shader: Option<String>, outputs: Vec<String>) {
+        self.shader_override = shader.map(|shader| {
+            let mut search_paths: Vec<PathBuf> = env::current_dir().into_iter().collect();
+            search_paths.extend(self.include_paths.iter().cloned());
+            match resolve_shader(&shader, &search_paths, &env_var) {
+                Ok(r) => {
+                    println!("--shader {shader} resolved to {r}");
+                    r
+                },
+                Err(e) => {
+                    println!("can't resolve --shader {shader}: {e}");
+                    shader
+                }
+            }
+        });
+        self.output_filter = outputs;
+    }
 
+    // None means the output shouldn't be drawn on.
+    pub fn monitor_config(&self, output: &OutputIdentity) -> Option<MonitorConfig> {
+        let name = &output.name;
+        if !self.output_filter.is_empty() && !self.output_filter.iter().any(|pattern| glob_match(pattern, name)) {
             return None;
         }
-        if let Ok(mut user_home) = env::var("HOME") {
-            user_home.push_str("/.config/gpuburden");
-            let path = PathBuf::from(user_home);
-            if path.exists() {
-                return Some(path);
-            }
 
-            return None;
+        let mut monitor = match self.matching_monitor(output) {
+            Some(r) => MonitorConfig {
+                name: name.to_string(),
+                ..r.clone()
+            },
+            // outputs missing from the config are only drawn on when there's no config, or --shader says to
+            None if !self.loaded || self.shader_override.is_some() => MonitorConfig {
+                name: name.to_string(),
+                shader: "default".to_string(),
+                include_paths: self.include_paths.clone(),
+                ..Default::default()
+            },
+            None => return None
+        };
+        if let Some(shader) = &self.shader_override {
+            monitor.shader = shader.clone();
         }
+        monitor.show_errors = Some(monitor.show_errors.unwrap_or(self.show_errors));
+        if monitor.seed.is_none() && let Some(span) = self.span(name) {
+            monitor.seed = self.spans[span].seed;
+        }
+        Some(monitor)
+    }
 
-        None
+    // The pointer is only asked for when some monitor uses it.
+    pub fn wants_pointer(&self) -> bool {
+        // transparent outputs let the pointer through, so they never see it
+        self.monitors.iter().any(|monitor| monitor.pointer && !monitor.transparent)
+    }
+
+    // Index of the first span the output is in.
+    pub fn span(&self, name: &str) -> Option<usize> {
+        self.spans.iter().position(|span| span.outputs.iter().any(|pattern| glob_match(pattern, name)))
+    }
+
+    // Entries matching make, model and so on beat ones that don't. Otherwise an exact name beats any
+    // pattern, then the pattern with the most characters that aren't wildcards wins, then whichever
+    // comes first in the file.
+    fn matching_monitor(&self, output: &OutputIdentity) -> Option<&MonitorConfig> {
+        let mut best: Option<((usize, usize), &MonitorConfig)> = None;
+        for monitor in &self.monitors {
+            let Some(specificity) = monitor.specificity(output) else {
+                continue;
+            };
+            if best.is_none_or(|(best_specificity, _)| specificity > best_specificity) {
+                best = Some((specificity, monitor));
+            }
+        }
+        best.map(|(_, monitor)| monitor)
+    }
+
+    pub fn stats_log_interval(&self) -> Option<Duration> {
+        self.stats_log_interval.filter(|secs| *secs > 0).map(Duration::from_secs)
+    }
+
+    // The first config folder that exists, for finding shaders when there's no config file.
+    pub fn locate_config_path() -> Option<PathBuf> {
+        Configuration::config_dirs().into_iter().find(|path| path.exists())
     }
     fn locate_config(config_path: &Path) -> Option<PathBuf> {
         let mut path = config_path.to_path_buf();
@@ -97,4 +350,355 @@ impl Configuration {
 
         None
     }
+
+    // Every folder gpuburden.toml is looked for in, the user's own first.
+    pub fn config_dirs() -> Vec<PathBuf> {
+        config_dirs_from(&env_var)
+    }
+    // Folders that hold shaders shared between users, searched after the config folder so
+    // distro packages and system images can ship shaders.
+    pub fn shader_dirs() -> Vec<PathBuf> {
+        shader_dirs_from(&env_var)
+    }
+    // The config folder, then the config's shader_paths, then the shared shader folders.
+    fn search_paths(config_path: &Path, shader_paths: &[String]) -> Vec<PathBuf> {
+        let mut paths = vec![config_path.to_path_buf()];
+        for path in shader_paths {
+            match expand_path(path, &env_var) {
+                Ok(r) => paths.push(config_path.join(r)),
+                Err(e) => println!("skipping shader path {path}: {e}")
+            }
+        }
+        for path in Configuration::shader_dirs() {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+    // Where includes are looked for when there's no config file, like for shaders from the command line.
+    pub fn include_paths() -> Vec<PathBuf> {
+        match Configuration::locate_config_path() {
+            Some(r) => Configuration::search_paths(&r, &[]),
+            None => Configuration::shader_dirs()
+        }
+    }
+}
+
+fn name_specificity(pattern: &str, name: &str) -> Option<usize> {
+    if !pattern.contains(['*', '?']) {
+        return (pattern == name).then_some(usize::MAX);
+    }
+    glob_match(pattern, name).then(|| pattern.chars().filter(|c| !matches!(c, '*' | '?')).count())
+}
+
+// `*` matches any run of characters and `?` any single one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // where the last * was and how much of the name it has eaten, to backtrack to
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+fn env_var(name: &str) -> Option<String> {
+    env::var(name).ok()
+}
+
+// Absolute paths (after expanding ~ and variables) are kept as they are, relative ones are looked for
+// in each search path in turn. If it isn't anywhere the first search path is used, so the error
+// says where it was expected.
+fn resolve_shader(shader: &str, search_paths: &[PathBuf], var: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    if shader.trim() == "default" {
+        return Ok(shader.to_string());
+    }
+    let expanded = PathBuf::from(expand_path(shader, var)?);
+    if expanded.is_absolute() {
+        return Ok(expanded.display().to_string());
+    }
+
+    let found = search_paths.iter().map(|path| path.join(&expanded)).find(|path| path.exists());
+    let path = match (found, search_paths.first()) {
+        (Some(r), _) => r,
+        (None, Some(first)) => first.join(&expanded),
+        (None, None) => expanded
+    };
+    Ok(path.display().to_string())
+}
+
+// Expands a leading ~ and any $VAR or ${VAR}, like a shell would.
+fn expand_path(path: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") {
+        expanded.push_str(&var("HOME").ok_or("~ is used but $HOME isn't set")?);
+        rest = &rest[1..];
+    }
+
+    while let Some(start) = rest.find('$') {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let (name, after) = match rest.strip_prefix('{') {
+            Some(braced) => {
+                let end = braced.find('}').ok_or_else(|| format!("missing }} in {path}"))?;
+                (&braced[..end], &braced[end + 1..])
+            },
+            None => {
+                let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        // a $ that isn't followed by a name is just a $
+        if name.is_empty() {
+            expanded.push('$');
+            continue;
+        }
+        expanded.push_str(&var(name).ok_or_else(|| format!("${name} isn't set"))?);
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn config_dirs_from(var: &dyn Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    let mut dirs = xdg_dirs(var, "XDG_CONFIG_HOME", ".config", "XDG_CONFIG_DIRS", "/etc/xdg");
+    // still checked if XDG_CONFIG_DIRS is set to something else
+    let system = PathBuf::from("/etc/xdg/gpuburden");
+    if !dirs.contains(&system) {
+        dirs.push(system);
+    }
+    dirs
+}
+
+fn shader_dirs_from(var: &dyn Fn(&str) -> Option<String>) -> Vec<PathBuf> {
+    xdg_dirs(var, "XDG_DATA_HOME", ".local/share", "XDG_DATA_DIRS", "/usr/local/share:/usr/share")
+        .into_iter()
+        .map(|path| path.join("shaders"))
+        .collect()
+}
+
+// XDG base directory lookup, each folder with gpuburden on the end. Unlike the spec, the default
+// home folder is still tried after $XDG_*_HOME in case that doesn't have a gpuburden folder.
+fn xdg_dirs(var: &dyn Fn(&str) -> Option<String>, home_var: &str, home_default: &str, dirs_var: &str, dirs_default: &str) -> Vec<PathBuf> {
+    // the spec says relative paths should be ignored
+    let absolute = |path: &str| Some(PathBuf::from(path)).filter(|path| path.is_absolute());
+
+    let mut dirs = Vec::new();
+    if let Some(path) = var(home_var).as_deref().and_then(absolute) {
+        dirs.push(path);
+    }
+    if let Some(home) = var("HOME").as_deref().and_then(absolute) {
+        dirs.push(home.join(home_default));
+    }
+    let system = var(dirs_var).filter(|dirs| !dirs.is_empty()).unwrap_or_else(|| dirs_default.to_string());
+    dirs.extend(system.split(':').filter_map(absolute));
+
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        let dir = dir.join("gpuburden");
+        if !unique.contains(&dir) {
+            unique.push(dir);
+        }
+    }
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::{Configuration, MonitorConfig, OutputIdentity};
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        move |name| vars.get(name).cloned()
+    }
+    fn output(name: &str) -> OutputIdentity {
+        OutputIdentity { name: name.to_string(), ..Default::default() }
+    }
+    fn paths(paths: &[&str]) -> Vec<PathBuf> {
+        paths.iter().map(PathBuf::from).collect()
+    }
+
+    fn loaded_config() -> Configuration {
+        Configuration {
+            monitors: vec![MonitorConfig { name: "DP-2".to_string(), shader: "/config/noise.wgsl".to_string(), seed: Some(1), ..Default::default() }],
+            loaded: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unlisted_outputs_only_drawn_without_config() {
+        assert!(loaded_config().monitor_config(&output("HDMI-A-1")).is_none());
+        assert_eq!(Configuration::default().monitor_config(&output("HDMI-A-1")).unwrap().shader, "default");
+    }
+
+    #[test]
+    fn shader_override_applies_to_every_output() {
+        let mut config = loaded_config();
+        config.apply_overrides(Some("/shaders/cli.wgsl".to_string()), Vec::new());
+        let listed = config.monitor_config(&output("DP-2")).unwrap();
+        assert_eq!(listed.shader, "/shaders/cli.wgsl");
+        assert_eq!(listed.seed, Some(1));
+        assert_eq!(config.monitor_config(&output("HDMI-A-1")).unwrap().shader, "/shaders/cli.wgsl");
+    }
+
+    #[test]
+    fn top_level_show_errors_applies_to_every_output() {
+        let mut config = loaded_config();
+        assert_eq!(config.monitor_config(&output("DP-2")).unwrap().show_errors, Some(false));
+        config.show_errors = true;
+        assert_eq!(config.monitor_config(&output("DP-2")).unwrap().show_errors, Some(true));
+        // a monitor can still turn it off for itself
+        config.monitors[0].show_errors = Some(false);
+        assert_eq!(config.monitor_config(&output("DP-2")).unwrap().show_errors, Some(false));
+    }
+
+    #[test]
+    fn output_filter_skips_other_outputs() {
+        let mut config = Configuration::default();
+        config.apply_overrides(None, vec!["DP-2".to_string()]);
+        assert!(config.monitor_config(&output("DP-2")).is_some());
+        assert!(config.monitor_config(&output("HDMI-A-1")).is_none());
+    }
+
+    #[test]
+    fn config_dirs_fall_back_to_home_and_system() {
+        let var = lookup(&[("HOME", "/home/a"), ("XDG_CONFIG_HOME", "/missing"), ("XDG_CONFIG_DIRS", "/opt/xdg:relative")]);
+        assert_eq!(super::config_dirs_from(&var), paths(&["/missing/gpuburden", "/home/a/.config/gpuburden", "/opt/xdg/gpuburden", "/etc/xdg/gpuburden"]));
+
+        let var = lookup(&[("HOME", "/home/a")]);
+        assert_eq!(super::config_dirs_from(&var), paths(&["/home/a/.config/gpuburden", "/etc/xdg/gpuburden"]));
+    }
+
+    #[test]
+    fn shader_dirs_use_data_dirs() {
+        let var = lookup(&[("HOME", "/home/a"), ("XDG_DATA_DIRS", "/usr/share:/opt/share")]);
+        assert_eq!(super::shader_dirs_from(&var), paths(&["/home/a/.local/share/gpuburden/shaders", "/usr/share/gpuburden/shaders", "/opt/share/gpuburden/shaders"]));
+
+        let var = lookup(&[]);
+        assert_eq!(super::shader_dirs_from(&var), paths(&["/usr/local/share/gpuburden/shaders", "/usr/share/gpuburden/shaders"]));
+    }
+
+    #[test]
+    fn expands_home_and_variables() {
+        let var = lookup(&[("HOME", "/home/a"), ("SHADERS", "/opt/shaders")]);
+        assert_eq!(super::expand_path("~/x.wgsl", &var).unwrap(), "/home/a/x.wgsl");
+        assert_eq!(super::expand_path("$SHADERS/x.wgsl", &var).unwrap(), "/opt/shaders/x.wgsl");
+        assert_eq!(super::expand_path("${HOME}/a$b/$", &lookup(&[("HOME", "/h"), ("b", "c")])).unwrap(), "/h/ac/$");
+        assert!(super::expand_path("$MISSING/x.wgsl", &var).is_err());
+    }
+
+    #[test]
+    fn resolves_shader_paths() {
+        let var = lookup(&[("HOME", "/home/a")]);
+        let config = std::env::temp_dir().join(format!("gpuburden-resolve-test-{}", std::process::id()));
+        let shared = config.join("shared");
+        let _ = std::fs::remove_dir_all(&config);
+        std::fs::create_dir_all(&shared).unwrap();
+        std::fs::write(shared.join("only-shared.wgsl"), "").unwrap();
+        let search_paths = vec![config.clone(), shared.clone()];
+
+        assert_eq!(super::resolve_shader("~/x.wgsl", &search_paths, &var).unwrap(), "/home/a/x.wgsl");
+        assert_eq!(super::resolve_shader("/abs/x.wgsl", &search_paths, &var).unwrap(), "/abs/x.wgsl");
+        assert_eq!(super::resolve_shader("only-shared.wgsl", &search_paths, &var).unwrap(), shared.join("only-shared.wgsl").display().to_string());
+        assert_eq!(super::resolve_shader("missing.wgsl", &search_paths, &var).unwrap(), config.join("missing.wgsl").display().to_string());
+        assert_eq!(super::resolve_shader("default", &search_paths, &var).unwrap(), "default");
+        std::fs::remove_dir_all(config).unwrap();
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(super::glob_match("*", "DP-2"));
+        assert!(super::glob_match("HDMI-*", "HDMI-A-1"));
+        assert!(super::glob_match("DP-?", "DP-2"));
+        assert!(super::glob_match("*-A-*", "HDMI-A-1"));
+        assert!(!super::glob_match("HDMI-*", "DP-2"));
+        assert!(!super::glob_match("DP-?", "DP-10"));
+    }
+
+    #[test]
+    fn most_specific_monitor_wins() {
+        let monitor = |name: &str, shader: &str| MonitorConfig { name: name.to_string(), shader: shader.to_string(), ..Default::default() };
+        let config = Configuration {
+            monitors: vec![monitor("*", "any"), monitor("HDMI-*", "hdmi"), monitor("HDMI-A-*", "hdmi-a"), monitor("HDMI-A-1", "exact")],
+            loaded: true,
+            ..Default::default()
+        };
+        assert_eq!(config.monitor_config(&output("HDMI-A-1")).unwrap().shader, "exact");
+        assert_eq!(config.monitor_config(&output("HDMI-A-2")).unwrap().shader, "hdmi-a");
+        assert_eq!(config.monitor_config(&output("HDMI-B-1")).unwrap().shader, "hdmi");
+        let fallback = config.monitor_config(&output("DP-2")).unwrap();
+        assert_eq!(fallback.shader, "any");
+        assert_eq!(fallback.name, "DP-2");
+    }
+
+    #[test]
+    fn matches_physical_monitor() {
+        let config = Configuration {
+            monitors: vec![
+                MonitorConfig { name: "DP-2".to_string(), shader: "by-name".to_string(), ..Default::default() },
+                MonitorConfig { name: "*".to_string(), make: Some("Dell Inc.".to_string()), serial: Some("ABC123".to_string()), shader: "by-serial".to_string(), ..Default::default() },
+                MonitorConfig { name: "*".to_string(), physical_size: Some([600, 340]), shader: "by-size".to_string(), ..Default::default() }
+            ],
+            loaded: true,
+            ..Default::default()
+        };
+        let dell = OutputIdentity {
+            name: "DP-2".to_string(),
+            make: "Dell Inc.".to_string(),
+            model: "DELL U2720Q".to_string(),
+            description: "Dell Inc. DELL U2720Q ABC123 (DP-2)".to_string(),
+            physical_size: (600, 340)
+        };
+        assert_eq!(config.monitor_config(&dell).unwrap().shader, "by-serial");
+
+        let other_dell = OutputIdentity { description: "Dell Inc. DELL U2720Q XYZ789 (DP-2)".to_string(), ..dell };
+        assert_eq!(config.monitor_config(&other_dell).unwrap().shader, "by-size");
+        assert_eq!(config.monitor_config(&output("DP-2")).unwrap().shader, "by-name");
+        assert!(config.monitor_config(&output("DP-3")).is_none());
+    }
+
+    #[test]
+    fn spans_share_a_seed() {
+        let config = Configuration {
+            spans: vec![super::SpanConfig { outputs: vec!["DP-*".to_string()], seed: Some(42) }],
+            ..Default::default()
+        };
+        assert_eq!(config.span("DP-1"), Some(0));
+        assert_eq!(config.span("HDMI-A-1"), None);
+        assert_eq!(config.monitor_config(&output("DP-2")).unwrap().seed, Some(42));
+        assert_eq!(config.monitor_config(&output("HDMI-A-1")).unwrap().seed, None);
+    }
+
+    #[test]
+    fn layer_size_stretches_between_anchored_edges() {
+        let background = MonitorConfig::default();
+        assert_eq!(background.layer_size((1920, 1080)), (0, 0));
+
+        let panel: MonitorConfig = toml::from_str("shader = \"panel.wgsl\"\nlayer = \"top\"\nanchor = [\"top\", \"left\", \"right\"]\nsize = [0, 32]\n").unwrap();
+        assert_eq!(panel.layer, super::LayerConfig::Top);
+        assert!(!panel.anchored(super::Edge::Bottom));
+        assert_eq!(panel.layer_size((1920, 1080)), (0, 32));
+
+        let strip: MonitorConfig = toml::from_str("shader = \"strip.wgsl\"\nanchor = [\"left\"]\n").unwrap();
+        assert_eq!(strip.layer_size((1920, 1080)), (1920, 1080));
+    }
 }
@@ -0,0 +1,45 @@
+use std::{fs::File, io::BufWriter, path::Path, process};
+
+use crate::{cli::RenderArgs, configuration::{Configuration, MonitorConfig}, renderer::Renderer};
+
+pub fn render(args: &RenderArgs) {
+    let (width, height) = args.size;
+    let config = shader_config(&args.shader, args.seed);
+    let mut renderer = match Renderer::headless(width, height, &config) {
+        Ok(r) => r,
+        Err(e) => exit_with(&e)
+    };
+
+    let pixels = renderer.render_frame(args.frame);
+    if let Err(e) = write_png(&args.output, width, height, &pixels) {
+        exit_with(&format!("failed to write {}: {e}", args.output.display()));
+    }
+    println!("wrote frame {} of {} to {}", args.frame, args.shader, args.output.display());
+}
+
+// Shaders given on the command line can still include files from the config and shared shader folders.
+pub fn shader_config(shader: &str, seed: Option<u32>) -> MonitorConfig {
+    MonitorConfig {
+        name: "headless".to_string(),
+        shader: shader.to_string(),
+        seed,
+        // nothing moves the pointer, but shaders that use it should still render
+        pointer: true,
+        include_paths: Configuration::include_paths(),
+        ..Default::default()
+    }
+}
+
+pub fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())
+}
+
+pub fn exit_with(message: &str) -> ! {
+    eprintln!("{message}");
+    process::exit(1);
+}
@@ -0,0 +1,399 @@
+use std::mem;
+
+use smithay_client_toolkit::{compositor::{CompositorHandler, CompositorState}, delegate_compositor, delegate_keyboard, delegate_output, delegate_registry, delegate_seat, delegate_session_lock, output::{OutputHandler, OutputState}, registry::{ProvidesRegistryState, RegistryState}, registry_handlers, seat::{keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers}, Capability, SeatHandler, SeatState}, session_lock::{SessionLock, SessionLockHandler, SessionLockState, SessionLockSurface, SessionLockSurfaceConfigure}};
+use wayland_client::{globals::registry_queue_init, protocol::{wl_keyboard::WlKeyboard, wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface}, Connection, QueueHandle};
+
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{auth::Authenticator, cli::LockArgs, configuration::{Configuration, MonitorConfig}, headless, renderer::{PromptBuffer, Renderer}, wayland};
+
+// `gpuburden lock`, the configured shaders on ext-session-lock surfaces with a password prompt on top.
+pub fn run(args: &LockArgs) {
+    let mut config = Configuration::load(args.config.as_deref());
+    config.apply_overrides(args.shader.clone(), Vec::new());
+    let authenticator = match crate::auth::Pam::new(&args.pam_service) {
+        Ok(r) => r,
+        Err(e) => headless::exit_with(&e)
+    };
+    if let Err(e) = start(config, Box::new(authenticator)) {
+        headless::exit_with(&e);
+    }
+}
+
+// What's been typed so far. Only the length ever leaves here, to draw the dots. The password is
+// zeroed whenever it's dropped, and never grown or shrunk in place since that would leave copies
+// of it behind in freed memory.
+#[derive(Default)]
+struct Prompt {
+    password: Zeroizing<String>,
+    checking: bool,
+    failed: bool
+}
+
+impl Prompt {
+    // Returns the password once enter is pressed.
+    fn key(&mut self, keysym: Keysym, text: Option<&str>) -> Option<Zeroizing<String>> {
+        match keysym {
+            Keysym::Return | Keysym::KP_Enter if !self.password.is_empty() => {
+                self.checking = true;
+                return Some(mem::take(&mut self.password));
+            },
+            Keysym::BackSpace => {
+                let end = self.password.char_indices().last().map(|(index, _)| index).unwrap_or_default();
+                self.password = copy_password(&self.password[..end], 0);
+            },
+            Keysym::Escape => self.password.zeroize(),
+            _ => {
+                let text: Zeroizing<String> = Zeroizing::new(text.unwrap_or_default().chars().filter(|c| !c.is_control()).collect());
+                if !text.is_empty() {
+                    if self.password.len() + text.len() > self.password.capacity() {
+                        self.password = copy_password(&self.password, text.len());
+                    }
+                    self.password.push_str(&text);
+                    self.failed = false;
+                }
+            }
+        }
+        None
+    }
+
+    fn check(&mut self, password: &str, authenticator: &mut dyn Authenticator) -> bool {
+        self.checking = false;
+        match authenticator.authenticate(password) {
+            Ok(()) => true,
+            Err(e) => {
+                println!("wrong password: {e}");
+                self.failed = true;
+                false
+            }
+        }
+    }
+
+    fn buffer(&self, scale: i32) -> PromptBuffer {
+        let state = match (self.checking, self.failed) {
+            (true, _) => 1,
+            (false, true) => 2,
+            (false, false) => 0
+        };
+        PromptBuffer { length: self.password.chars().count() as u32, state, scale: scale as f32, ..Default::default() }
+    }
+}
+
+// The password in a new buffer with room for `extra` more bytes and then some, so the old buffer
+// can be zeroed as it's dropped.
+fn copy_password(password: &str, extra: usize) -> Zeroizing<String> {
+    let mut copy = Zeroizing::new(String::with_capacity(((password.len() + extra) * 2).max(64)));
+    copy.push_str(password);
+    copy
+}
+
+struct LockState {
+    unlocked: bool,
+    error: Option<String>,
+    config: Configuration,
+    lock: Option<SessionLock>,
+    targets: Vec<LockTarget>,
+    keyboard: Option<WlKeyboard>,
+    prompt: Prompt,
+    authenticator: Box<dyn Authenticator>,
+
+    conn: Connection,
+    compositor: CompositorState,
+    session_lock_state: SessionLockState,
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState
+}
+struct LockTarget {
+    name: String,
+    output: WlOutput,
+    surface: SessionLockSurface,
+    renderer: Option<Renderer>,
+    scale: i32,
+    size: (u32, u32)
+}
+
+impl LockState {
+    // Every output has to get a lock surface, ones the config skips get the default shader.
+    fn add_output(&mut self, qh: &QueueHandle<Self>, output: WlOutput) {
+        let Some(lock) = &self.lock else {
+            return;
+        };
+        let Some(info) = self.output_state.info(&output) else {
+            return;
+        };
+        let name = info.name.clone().unwrap_or_default();
+        let surface = lock.create_lock_surface(self.compositor.create_surface(qh), &output, qh);
+        println!("locking {name}");
+        self.targets.push(LockTarget { name, output, surface, renderer: None, scale: info.scale_factor.max(1), size: (0, 0) });
+    }
+
+    fn draw(&mut self, qh: &QueueHandle<Self>, surface: &WlSurface) {
+        for target in self.targets.iter_mut().filter(|target| target.surface.wl_surface() == surface) {
+            let Some(renderer) = &mut target.renderer else {
+                continue;
+            };
+            renderer.prompt = Some(self.prompt.buffer(target.scale));
+            renderer.draw();
+            surface.frame(qh, surface.clone());
+            surface.commit();
+        }
+    }
+
+    // Draws the prompt as it is now on every output straight away, since checking the password blocks.
+    fn redraw_all(&mut self) {
+        for target in &mut self.targets {
+            if let Some(renderer) = &mut target.renderer {
+                renderer.prompt = Some(self.prompt.buffer(target.scale));
+                renderer.draw();
+                target.surface.wl_surface().commit();
+            }
+        }
+        let _ = self.conn.flush();
+    }
+
+    fn key(&mut self, event: KeyEvent) {
+        let Some(password) = self.prompt.key(event.keysym, event.utf8.as_deref()) else {
+            return;
+        };
+        self.redraw_all();
+        if self.prompt.check(&password, self.authenticator.as_mut()) {
+            self.unlocked = true;
+        }
+    }
+
+    fn resize(target: &mut LockTarget) {
+        let (width, height) = target.size;
+        let Some(renderer) = &mut target.renderer else {
+            return;
+        };
+        target.surface.wl_surface().set_buffer_scale(target.scale);
+        renderer.configure_surface(width * target.scale as u32, height * target.scale as u32);
+        renderer.output.scale = target.scale as f32;
+    }
+}
+
+impl SessionLockHandler for LockState {
+    fn locked(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _session_lock: SessionLock) {
+        println!("locked");
+    }
+
+    fn finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _session_lock: SessionLock) {
+        // the compositor said no, most likely something else has already locked the session
+        self.error = Some("the compositor refused to lock the session, is it already locked?".to_string());
+    }
+
+    fn configure(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, surface: SessionLockSurface, configure: SessionLockSurfaceConfigure, _serial: u32) {
+        let Some(target) = self.targets.iter_mut().find(|target| target.surface.wl_surface() == surface.wl_surface()) else {
+            return;
+        };
+        target.size = configure.new_size;
+        if target.renderer.is_none() {
+            let info = self.output_state.info(&target.output);
+            // lock surfaces can't be see-through
+            let config = info.as_ref().and_then(wayland::output_identity).and_then(|identity| self.config.monitor_config(&identity)).map(|config| MonitorConfig { transparent: false, ..config });
+            let (raw_display_handle, raw_window_handle) = wayland::raw_handles(&self.conn, surface.wl_surface());
+            let mut renderer = Renderer::for_layer(raw_display_handle, raw_window_handle, &config);
+            renderer.prompt = Some(self.prompt.buffer(target.scale));
+            renderer.transform = info.map(|info| u32::from(info.transform)).unwrap_or_default();
+            target.renderer = Some(renderer);
+            LockState::resize(target);
+            println!("{} configured for {}x{}", target.name, target.size.0, target.size.1);
+            self.draw(qh, surface.wl_surface());
+            return;
+        }
+        LockState::resize(target);
+    }
+}
+
+impl CompositorHandler for LockState {
+    fn frame(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, surface: &WlSurface, _time: u32) {
+        self.draw(qh, surface);
+    }
+    fn scale_factor_changed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, surface: &WlSurface, new_factor: i32) {
+        for target in self.targets.iter_mut().filter(|target| target.surface.wl_surface() == surface) {
+            target.scale = new_factor;
+            LockState::resize(target);
+        }
+    }
+    fn transform_changed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, _new_transform: wayland_client::protocol::wl_output::Transform) {}
+    fn surface_enter(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, _output: &WlOutput) {}
+    fn surface_leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, _output: &WlOutput) {}
+}
+
+impl OutputHandler for LockState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+    // outputs plugged in while locked need covering too
+    fn new_output(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, output: WlOutput) {
+        self.add_output(qh, output);
+    }
+    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        let Some(index) = self.targets.iter().position(|target| target.output == output) else {
+            return;
+        };
+        let target = self.targets.remove(index);
+        // the wgpu surface points at the lock surface, so it has to go before the rest of the target
+        if let Some(renderer) = target.renderer {
+            renderer.free_surface();
+        }
+    }
+}
+
+impl SeatHandler for LockState {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+    fn new_capability(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, seat: WlSeat, capability: Capability) {
+        if capability != Capability::Keyboard || self.keyboard.is_some() {
+            return;
+        }
+        match self.seat_state.get_keyboard(qh, &seat, None) {
+            Ok(r) => self.keyboard = Some(r),
+            Err(e) => println!("failed to get the keyboard: {e}")
+        }
+    }
+    fn remove_capability(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat, capability: Capability) {
+        if capability == Capability::Keyboard && let Some(keyboard) = self.keyboard.take() {
+            keyboard.release();
+        }
+    }
+    fn new_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat) {}
+    fn remove_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat) {}
+}
+
+impl KeyboardHandler for LockState {
+    fn press_key(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, event: KeyEvent) {
+        self.key(event);
+    }
+    fn enter(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _surface: &WlSurface, _serial: u32, _raw: &[u32], _keysyms: &[Keysym]) {}
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _surface: &WlSurface, _serial: u32) {}
+    // holding backspace or a key repeats it like in any other text field
+    fn repeat_key(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, event: KeyEvent) {
+        self.key(event);
+    }
+    fn release_key(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, _event: KeyEvent) {}
+    fn update_modifiers(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, _modifiers: Modifiers, _raw_modifiers: RawModifiers, _layout: u32) {}
+}
+
+impl ProvidesRegistryState for LockState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(LockState);
+delegate_output!(LockState);
+delegate_seat!(LockState);
+delegate_keyboard!(LockState);
+delegate_session_lock!(LockState);
+delegate_registry!(LockState);
+
+pub fn start(config: Configuration, authenticator: Box<dyn Authenticator>) -> Result<(), String> {
+    let conn = Connection::connect_to_env().map_err(|e| format!("unable to connect to a compositor: {e}"))?;
+    let (globals, mut event_queue) = registry_queue_init(&conn).map_err(|e| e.to_string())?;
+    let qh = event_queue.handle();
+
+    let mut state = LockState {
+        unlocked: false,
+        error: None,
+        config,
+        lock: None,
+        targets: Vec::new(),
+        keyboard: None,
+        prompt: Prompt::default(),
+        authenticator,
+
+        conn: conn.clone(),
+        compositor: CompositorState::bind(&globals, &qh).map_err(|_| "compositor does not support 'wl_compositor'".to_string())?,
+        session_lock_state: SessionLockState::new(&globals, &qh),
+        registry_state: RegistryState::new(&globals),
+        seat_state: SeatState::new(&globals, &qh),
+        output_state: OutputState::new(&globals, &qh)
+    };
+
+    // find out about the outputs first, every one of them has to be covered
+    event_queue.roundtrip(&mut state).map_err(|e| e.to_string())?;
+    state.lock = Some(state.session_lock_state.lock(&qh).map_err(|_| "compositor does not support 'ext_session_lock_manager_v1'".to_string())?);
+    for output in state.output_state.outputs().collect::<Vec<_>>() {
+        state.add_output(&qh, output);
+    }
+
+    while !state.unlocked && state.error.is_none() {
+        event_queue.blocking_dispatch(&mut state).map_err(|e| e.to_string())?;
+    }
+
+    if let Some(lock) = state.lock.take() && state.unlocked {
+        lock.unlock();
+        // make sure the compositor has the unlock before exiting, otherwise it stays locked
+        conn.roundtrip().map_err(|e| e.to_string())?;
+        println!("unlocked");
+    }
+    for target in state.targets.drain(..) {
+        if let Some(renderer) = target.renderer {
+            renderer.free_surface();
+        }
+    }
+    match state.error {
+        Some(e) => Err(e),
+        None => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smithay_client_toolkit::seat::keyboard::Keysym;
+
+    use super::Prompt;
+    use crate::auth::StubAuthenticator;
+
+    fn type_text(prompt: &mut Prompt, text: &str) {
+        for character in text.chars() {
+            assert!(prompt.key(Keysym::NoSymbol, Some(&character.to_string())).is_none());
+        }
+    }
+
+    #[test]
+    fn unlocks_with_the_right_password() {
+        let mut authenticator = StubAuthenticator { password: "hunter2".to_string(), attempts: 0 };
+        let mut prompt = Prompt::default();
+
+        type_text(&mut prompt, "hunter3");
+        let password = prompt.key(Keysym::Return, None).expect("enter should submit");
+        assert!(!prompt.check(&password, &mut authenticator));
+        assert_eq!(prompt.buffer(1).state, 2);
+        assert_eq!(prompt.buffer(1).length, 0);
+
+        type_text(&mut prompt, "hunter3");
+        prompt.key(Keysym::BackSpace, None);
+        type_text(&mut prompt, "2");
+        assert_eq!(prompt.buffer(1).state, 0);
+        let password = prompt.key(Keysym::KP_Enter, None).expect("enter should submit");
+        assert!(prompt.check(&password, &mut authenticator));
+        assert_eq!(authenticator.attempts, 2);
+    }
+
+    #[test]
+    fn long_passwords_survive_moving_buffers() {
+        let mut authenticator = StubAuthenticator { password: "ü".repeat(100), attempts: 0 };
+        let mut prompt = Prompt::default();
+        type_text(&mut prompt, &"ü".repeat(101));
+        prompt.key(Keysym::BackSpace, None);
+        assert_eq!(prompt.buffer(1).length, 100);
+        let password = prompt.key(Keysym::Return, None).expect("enter should submit");
+        assert!(prompt.check(&password, &mut authenticator));
+    }
+
+    #[test]
+    fn ignores_empty_passwords_and_control_characters() {
+        let mut prompt = Prompt::default();
+        assert!(prompt.key(Keysym::Return, Some("\r")).is_none());
+        type_text(&mut prompt, "ab");
+        prompt.key(Keysym::Escape, Some("\u{1b}"));
+        assert_eq!(prompt.buffer(1).length, 0);
+        assert!(prompt.key(Keysym::Return, Some("\r")).is_none());
+    }
+}
@@ -1,20 +1,73 @@
-use std::{panic, time::Instant};
+use std::{panic, sync::atomic::{AtomicBool, Ordering}, time::Instant};
 
-use crate::configuration::Configuration;
+use clap::Parser;
 
+use crate::{cli::{Cli, Command}, configuration::Configuration, stats::StatsRegistry};
+
+// println! that only prints with --verbose
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if crate::VERBOSE.load(std::sync::atomic::Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "lock")]
+mod auth;
+mod bench;
+mod cli;
 mod configuration;
+mod font;
+mod headless;
+#[cfg(feature = "lock")]
+mod lock;
+mod preprocessor;
+mod preview;
+mod record;
 mod renderer;
+mod stats;
+mod stdlib;
+mod sys;
+mod timing;
+mod uniforms;
+mod validation;
 mod wayland;
 
 const ERROR_TIMEOUT_SECS: u64 = 30;
 
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
 fn main() {
-    let config = Configuration::load();
+    let cli = Cli::parse();
+    VERBOSE.store(cli.verbose, Ordering::Relaxed);
+    match &cli.command {
+        Some(Command::Render(args)) => headless::render(args),
+        Some(Command::Record(args)) => record::record(args),
+        Some(Command::Bench(args)) => bench::bench(args),
+        Some(Command::Stats) => match stats::query() {
+            Ok(report) => print!("{report}"),
+            Err(e) => headless::exit_with(&e)
+        },
+        #[cfg(feature = "lock")]
+        Some(Command::Lock(args)) => lock::run(args),
+        Some(Command::Preview(args)) => preview::run(args),
+        None => run_daemon(cli)
+    }
+}
+
+fn run_daemon(cli: Cli) {
+    let mut config = Configuration::load(cli.config.as_deref());
+    config.apply_overrides(cli.shader, cli.outputs);
+    let stats = StatsRegistry::default();
+    if let Err(e) = stats.serve() {
+        headless::exit_with(&e);
+    }
 
     let mut last_error: Instant = Instant::now();
     loop {
         let result = panic::catch_unwind(|| {
-            wayland::start(config.clone());
+            wayland::start(config.clone(), stats.clone());
         });
 
         if result.is_ok() {
@@ -0,0 +1,314 @@
+use std::{collections::HashSet, fmt::Display, fs, path::{Path, PathBuf}};
+
+use naga::{front::wgsl, valid::{Capabilities, ValidationFlags, Validator}, Span};
+
+use crate::stdlib;
+
+// Resolves `#include "file.wgsl"` and `#import "file.wgsl"` lines before the source is handed to wgpu.
+// A bare name like `#import noise` pulls in one of the built-in modules from `stdlib` instead.
+// Each file is only ever pasted in once (like an include guard), so two files can safely include
+// the same helpers. Every output line remembers where it came from so naga errors can be reported
+// against the file the user actually wrote.
+pub struct ProcessedShader {
+    pub source: String,
+    // every file on disk that went into it, for noticing when it changes
+    pub files: Vec<PathBuf>,
+    origins: Vec<LineOrigin>
+}
+#[derive(Clone)]
+struct LineOrigin {
+    file: String,
+    line: usize
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    Read { file: String, error: std::io::Error },
+    NotFound { name: String, file: String, line: usize },
+    Cycle { chain: Vec<String> },
+    BadDirective { file: String, line: usize, text: String }
+}
+impl Display for PreprocessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PreprocessError::Read { file, error } => write!(f, "failed to read {file}: {error}"),
+            PreprocessError::NotFound { name, file, line } => write!(f, "{file}:{line}: could not find \"{name}\" to include"),
+            PreprocessError::Cycle { chain } => write!(f, "include cycle: {}", chain.join(" -> ")),
+            PreprocessError::BadDirective { file, line, text } => write!(f, "{file}:{line}: malformed directive `{text}`, expected `#include \"file.wgsl\"` or `#import module`")
+        }
+    }
+}
+
+struct Preprocessor<'a> {
+    search_paths: &'a [PathBuf],
+    included: HashSet<String>,
+    stack: Vec<String>,
+    output: String,
+    origins: Vec<LineOrigin>
+}
+
+pub fn preprocess(path: &Path, search_paths: &[PathBuf]) -> Result<ProcessedShader, PreprocessError> {
+    let mut preprocessor = Preprocessor::new(search_paths);
+    preprocessor.include_file(path)?;
+    Ok(preprocessor.finish())
+}
+
+// Same as `preprocess` but for source that doesn't live on disk, like the built-in default shader.
+pub fn preprocess_source(name: &str, source: &str, search_paths: &[PathBuf]) -> Result<ProcessedShader, PreprocessError> {
+    let mut preprocessor = Preprocessor::new(search_paths);
+    preprocessor.enter(name.to_string())?;
+    preprocessor.process(name, source, None)?;
+    Ok(preprocessor.finish())
+}
+
+impl<'a> Preprocessor<'a> {
+    fn new(search_paths: &'a [PathBuf]) -> Self {
+        Self {
+            search_paths,
+            included: HashSet::new(),
+            stack: Vec::new(),
+            output: String::new(),
+            origins: Vec::new()
+        }
+    }
+
+    fn finish(self) -> ProcessedShader {
+        let mut files: Vec<PathBuf> = self.included.into_iter().filter(|key| !key.starts_with("gpuburden:")).map(PathBuf::from).filter(|path| path.exists()).collect();
+        files.sort();
+        ProcessedShader {
+            source: self.output,
+            files,
+            origins: self.origins
+        }
+    }
+
+    // Returns false if this source was already included somewhere, in which case it should be skipped.
+    fn enter(&mut self, key: String) -> Result<bool, PreprocessError> {
+        if self.stack.contains(&key) {
+            let mut chain = self.stack.clone();
+            chain.push(key);
+            return Err(PreprocessError::Cycle { chain });
+        }
+        if !self.included.insert(key.clone()) {
+            return Ok(false);
+        }
+        self.stack.push(key);
+        Ok(true)
+    }
+
+    fn include_file(&mut self, path: &Path) -> Result<(), PreprocessError> {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if !self.enter(canonical.display().to_string())? {
+            return Ok(());
+        }
+
+        let file = path.display().to_string();
+        let contents = fs::read_to_string(path).map_err(|error| PreprocessError::Read { file: file.clone(), error })?;
+        self.process(&file, &contents, path.parent())
+    }
+
+    fn include_builtin(&mut self, name: &str, file: &str, line: usize) -> Result<(), PreprocessError> {
+        let contents = match stdlib::module(name) {
+            Some(r) => r,
+            None => return Err(PreprocessError::NotFound { name: name.to_string(), file: file.to_string(), line })
+        };
+        let key = format!("gpuburden:{name}");
+        if !self.enter(key.clone())? {
+            return Ok(());
+        }
+
+        self.process(&key, contents, None)
+    }
+
+    fn process(&mut self, file: &str, contents: &str, directory: Option<&Path>) -> Result<(), PreprocessError> {
+        for (index, line) in contents.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim();
+            let directive = directive_argument(trimmed, "#include").or_else(|| directive_argument(trimmed, "#import"));
+            let Some(argument) = directive else {
+                self.output.push_str(line);
+                self.output.push('\n');
+                self.origins.push(LineOrigin { file: file.to_string(), line: line_number });
+                continue;
+            };
+
+            if let Some(name) = parse_module_name(argument) {
+                self.include_builtin(name, file, line_number)?;
+                continue;
+            }
+            let name = match parse_quoted(argument) {
+                Some(r) => r,
+                None => return Err(PreprocessError::BadDirective { file: file.to_string(), line: line_number, text: trimmed.to_string() })
+            };
+            let resolved = match self.resolve(name, directory) {
+                Some(r) => r,
+                None => return Err(PreprocessError::NotFound { name: name.to_string(), file: file.to_string(), line: line_number })
+            };
+            self.include_file(&resolved)?;
+        }
+        self.stack.pop();
+
+        Ok(())
+    }
+
+    fn resolve(&self, name: &str, directory: Option<&Path>) -> Option<PathBuf> {
+        let name = Path::new(name);
+        if name.is_absolute() {
+            return name.exists().then(|| name.to_path_buf());
+        }
+
+        // relative to the including file first, then the config directory and friends
+        directory.into_iter()
+            .chain(self.search_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.exists())
+    }
+}
+
+// The rest of the line after `keyword`, as long as the keyword is a whole word, so `#includes`
+// isn't taken for `#include`.
+fn directive_argument<'a>(line: &'a str, keyword: &str) -> Option<&'a str> {
+    let argument = line.strip_prefix(keyword)?;
+    match argument.chars().next() {
+        None => Some(argument),
+        Some(c) if c.is_whitespace() => Some(argument),
+        Some(_) => None
+    }
+}
+fn parse_module_name(argument: &str) -> Option<&str> {
+    let argument = argument.trim();
+    if argument.is_empty() || !argument.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(argument)
+}
+fn parse_quoted(argument: &str) -> Option<&str> {
+    let argument = argument.trim();
+    let inner = argument.strip_prefix('"')?.strip_suffix('"')?;
+    if inner.is_empty() || inner.contains('"') {
+        return None;
+    }
+    Some(inner)
+}
+
+impl ProcessedShader {
+    pub fn prepend(&mut self, text: &str, file: &str) {
+        let origins = text.lines().enumerate().map(|(index, _)| LineOrigin { file: file.to_string(), line: index + 1 });
+        self.origins.splice(0..0, origins);
+        self.source.insert_str(0, text);
+    }
+
+    // Parses and validates the shader with naga, returning a readable error pointing into the
+    // original files when it isn't valid WGSL.
+    pub fn validate(&self) -> Result<naga::Module, String> {
+        let module = wgsl::parse_str(&self.source).map_err(|err| {
+            self.format_error(err.message(), err.labels().map(|(span, label)| (span, label.to_string())))
+        })?;
+
+        Validator::new(ValidationFlags::all(), Capabilities::default())
+            .validate(&module)
+            .map_err(|err| self.format_error(&err.as_inner().to_string(), err.spans().cloned()))?;
+
+        Ok(module)
+    }
+
+    fn format_error(&self, message: &str, labels: impl Iterator<Item = (Span, String)>) -> String {
+        let mut output = format!("error: {message}");
+        for (span, label) in labels {
+            if !span.is_defined() {
+                continue;
+            }
+            let location = span.location(&self.source);
+            let position = match self.origins.get((location.line_number as usize).saturating_sub(1)) {
+                Some(origin) => format!("{}:{}:{}", origin.file, origin.line, location.line_position),
+                None => format!("<shader>:{}:{}", location.line_number, location.line_position)
+            };
+            output.push_str(&format!("\n  --> {position}"));
+            if !label.is_empty() {
+                output.push_str(&format!(": {label}"));
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use super::{preprocess_source, PreprocessError};
+
+    // a fresh directory of shader files for one test
+    fn write_files(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("gpuburden-preprocessor-{test}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        for (name, contents) in files {
+            fs::write(directory.join(name), contents).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn files_are_only_included_once() {
+        let directory = write_files("guard", &[
+            ("a.wgsl", "#include \"common.wgsl\"\nconst A = 1;"),
+            ("common.wgsl", "const COMMON = 0;")
+        ]);
+        let source = "#include \"a.wgsl\"\n#include \"common.wgsl\"\nconst MAIN = 2;";
+        let processed = preprocess_source("main.wgsl", source, std::slice::from_ref(&directory)).unwrap();
+        assert_eq!(processed.source, "const COMMON = 0;\nconst A = 1;\nconst MAIN = 2;\n");
+        assert_eq!(processed.files, vec![directory.join("a.wgsl").canonicalize().unwrap(), directory.join("common.wgsl").canonicalize().unwrap()]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn include_cycles_are_reported() {
+        let directory = write_files("cycle", &[
+            ("a.wgsl", "#include \"b.wgsl\""),
+            ("b.wgsl", "#include \"a.wgsl\"")
+        ]);
+        let result = preprocess_source("main.wgsl", "#include \"a.wgsl\"", std::slice::from_ref(&directory));
+        let a = directory.join("a.wgsl").canonicalize().unwrap().display().to_string();
+        let b = directory.join("b.wgsl").canonicalize().unwrap().display().to_string();
+        match result {
+            Err(PreprocessError::Cycle { chain }) => assert_eq!(chain, vec!["main.wgsl".to_string(), a.clone(), b, a]),
+            _ => panic!("expected a cycle")
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn missing_includes_point_at_the_directive() {
+        let directory = write_files("missing", &[("a.wgsl", "const A = 1;\n\n#include \"nope.wgsl\"")]);
+        let a = directory.join("a.wgsl").display().to_string();
+        match preprocess_source("main.wgsl", "#include \"a.wgsl\"", std::slice::from_ref(&directory)) {
+            Err(PreprocessError::NotFound { name, file, line }) => {
+                assert_eq!(name, "nope.wgsl");
+                assert_eq!(file, a);
+                assert_eq!(line, 3);
+            },
+            _ => panic!("expected the include to be missing")
+        }
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn directives_have_to_be_whole_words() {
+        let processed = preprocess_source("main.wgsl", "#importance\n#includes \"x\"", &[]).unwrap();
+        assert_eq!(processed.source, "#importance\n#includes \"x\"\n");
+        assert!(matches!(preprocess_source("main.wgsl", "#include", &[]), Err(PreprocessError::BadDirective { line: 1, .. })));
+    }
+
+    #[test]
+    fn errors_point_at_the_original_file() {
+        let directory = write_files("origin", &[("helpers.wgsl", "const A = 1;\nconst B = undefined_thing;")]);
+        let helpers = directory.join("helpers.wgsl").display().to_string();
+        let source = "// main\n#include \"helpers.wgsl\"\nconst C = 2;";
+        let mut processed = preprocess_source("main.wgsl", source, std::slice::from_ref(&directory)).unwrap();
+        processed.prepend("const PRELUDE = 0;\n", "<prelude>");
+        let error = processed.validate().unwrap_err();
+        assert!(error.contains(&format!("--> {helpers}:2:")), "{error}");
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
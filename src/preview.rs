@@ -0,0 +1,381 @@
+use std::{fs, path::{Path, PathBuf}, time::{Duration, Instant, SystemTime}};
+
+use smithay_client_toolkit::{compositor::{CompositorHandler, CompositorState}, delegate_compositor, delegate_keyboard, delegate_output, delegate_pointer, delegate_registry, delegate_seat, delegate_xdg_shell, delegate_xdg_window, output::{OutputHandler, OutputState}, registry::{ProvidesRegistryState, RegistryState}, registry_handlers, seat::{keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers, RawModifiers}, pointer::{PointerEvent, PointerEventKind, PointerHandler}, Capability, SeatHandler, SeatState}, shell::{xdg::{window::{Window, WindowConfigure, WindowDecorations, WindowHandler}, XdgShell}, WaylandSurface}};
+use wayland_client::{globals::registry_queue_init, protocol::{wl_keyboard::WlKeyboard, wl_output::WlOutput, wl_pointer::WlPointer, wl_seat::WlSeat, wl_surface::WlSurface}, Connection, QueueHandle};
+
+use crate::{cli::PreviewArgs, configuration::MonitorConfig, headless, preprocessor, renderer::{Renderer, ScrubberBuffer}, wayland};
+
+// has to match HEIGHT in shaders/scrubber.wgsl
+const SCRUBBER_HEIGHT: f64 = 12.0;
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// `gpuburden preview`, the shader in a normal window that reloads whenever the file is saved.
+pub fn run(args: &PreviewArgs) {
+    let mut config = headless::shader_config(&args.shader, args.seed);
+    // so a shader that doesn't compile at startup has its error on screen like after a reload
+    config.show_errors = Some(true);
+    let loop_frames = (args.duration * 60.0).round().max(1.0) as u32;
+    if let Err(e) = start(config, args.size, loop_frames) {
+        headless::exit_with(&e);
+    }
+}
+
+// Which frame is on screen. Time in the preview comes from the frame number like it does when
+// recording, so pausing and scrubbing are exact.
+struct Playback {
+    // in frames at 60 a second, fractional so it keeps up with displays that aren't 60Hz
+    position: f64,
+    paused: bool,
+    // frames before it loops back to the start
+    length: u32
+}
+
+impl Playback {
+    fn advance(&mut self, elapsed: Duration) {
+        if !self.paused {
+            self.position = (self.position + elapsed.as_secs_f64() * 60.0) % self.length as f64;
+        }
+    }
+    fn step(&mut self, frames: i64) {
+        self.paused = true;
+        self.position = (self.frame() as i64 + frames).rem_euclid(self.length as i64) as f64;
+    }
+    // position is from 0 to 1 along the scrubber
+    fn seek(&mut self, position: f64) {
+        self.position = ((position.clamp(0.0, 1.0) * self.length as f64) as u32).min(self.length - 1) as f64;
+    }
+    fn frame(&self) -> u32 {
+        self.position as u32
+    }
+    fn progress(&self) -> f32 {
+        self.frame() as f32 / self.length as f32
+    }
+}
+
+struct PreviewState {
+    close: bool,
+    config: MonitorConfig,
+    window: Window,
+    renderer: Option<Renderer>,
+    size: (u32, u32),
+    scale: i32,
+    playback: Playback,
+    shift: bool,
+    // where the pointer is in the window, and whether it's dragging the scrubber
+    pointer_position: (f64, f64),
+    scrubbing: bool,
+    watched: Vec<(PathBuf, Option<SystemTime>)>,
+    last_reload_check: Instant,
+    last_draw: Instant,
+    keyboard: Option<WlKeyboard>,
+    pointer: Option<WlPointer>,
+
+    conn: Connection,
+    registry_state: RegistryState,
+    seat_state: SeatState,
+    output_state: OutputState
+}
+
+impl PreviewState {
+    fn draw(&mut self, qh: &QueueHandle<Self>) {
+        if self.last_reload_check.elapsed() >= RELOAD_CHECK_INTERVAL {
+            self.last_reload_check = Instant::now();
+            if self.watched.iter().any(|(path, modified)| modified_time(path) != *modified) {
+                self.reload();
+            }
+        }
+        let elapsed = self.last_draw.elapsed();
+        self.last_draw = Instant::now();
+        if !self.scrubbing {
+            self.playback.advance(elapsed);
+        }
+
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        renderer.scrubber = Some(ScrubberBuffer { progress: self.playback.progress(), scale: self.scale as f32, paused: self.playback.paused as u32, ..Default::default() });
+        renderer.draw_frame(self.playback.frame());
+        let surface = self.window.wl_surface();
+        surface.frame(qh, surface.clone());
+        surface.commit();
+    }
+
+    // Recompiles the shader, leaving the last working version running with the error over the top
+    // if it doesn't compile.
+    fn reload(&mut self) {
+        self.update_watched();
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        match renderer.reload_shader(&self.config) {
+            Ok(()) => {
+                println!("reloaded {}", self.config.shader);
+                renderer.set_overlay_text(None);
+            },
+            Err(e) => {
+                println!("{e}");
+                renderer.set_overlay_text(Some(e));
+            }
+        }
+    }
+
+    // Finds every file the shader is made of, to notice when one of them is saved.
+    fn update_watched(&mut self) {
+        let shader = Path::new(&self.config.shader);
+        let files = match preprocessor::preprocess(shader, &self.config.include_paths) {
+            Ok(r) => r.files,
+            // keep watching what was there before as well, in case it's an include that's broken
+            Err(_) => self.watched.iter().map(|(path, _)| path.clone()).chain([shader.to_path_buf()]).collect()
+        };
+        self.watched = files.into_iter().map(|path| {
+            let modified = modified_time(&path);
+            (path, modified)
+        }).collect();
+    }
+
+    fn resize(&mut self) {
+        let Some(renderer) = &mut self.renderer else {
+            return;
+        };
+        self.window.wl_surface().set_buffer_scale(self.scale);
+        renderer.output.scale = self.scale as f32;
+        renderer.configure_surface(self.size.0 * self.scale as u32, self.size.1 * self.scale as u32);
+    }
+
+    fn update_title(&self) {
+        let state = match self.playback.paused {
+            true => format!("paused at frame {} ({:.2}s)", self.playback.frame(), self.playback.frame() as f32 / 60.0),
+            false => "playing".to_string()
+        };
+        self.window.set_title(format!("{} - {state} - gpuburden", self.config.shader));
+    }
+
+    fn seek_to_pointer(&mut self) {
+        self.playback.seek(self.pointer_position.0 / self.size.0.max(1) as f64);
+        self.update_title();
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl WindowHandler for PreviewState {
+    fn request_close(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _window: &Window) {
+        self.close = true;
+    }
+
+    fn configure(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, _window: &Window, configure: WindowConfigure, _serial: u32) {
+        // no size means it's up to us
+        if let (Some(width), Some(height)) = configure.new_size {
+            self.size = (width.get(), height.get());
+        }
+        if self.renderer.is_some() {
+            self.resize();
+            return;
+        }
+
+        let (raw_display_handle, raw_window_handle) = wayland::raw_handles(&self.conn, self.window.wl_surface());
+        let mut renderer = Renderer::for_layer(raw_display_handle, raw_window_handle, &Some(self.config.clone()));
+        // time comes from the frame number instead
+        renderer.time_base = None;
+        // has to be there before the pipeline is built for the bar to be drawn
+        renderer.scrubber = Some(ScrubberBuffer::default());
+        self.renderer = Some(renderer);
+        self.resize();
+        // the renderer has just compiled the shader, so only the files need looking at
+        self.update_watched();
+        self.update_title();
+        self.draw(qh);
+    }
+}
+
+impl CompositorHandler for PreviewState {
+    fn frame(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, _surface: &WlSurface, _time: u32) {
+        self.draw(qh);
+    }
+    fn scale_factor_changed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, new_factor: i32) {
+        self.scale = new_factor;
+        self.resize();
+    }
+    fn transform_changed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, _new_transform: wayland_client::protocol::wl_output::Transform) {}
+    fn surface_enter(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, _output: &WlOutput) {}
+    fn surface_leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _surface: &WlSurface, _output: &WlOutput) {}
+}
+
+impl OutputHandler for PreviewState {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+    fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {}
+}
+
+impl SeatHandler for PreviewState {
+    fn seat_state(&mut self) -> &mut SeatState {
+        &mut self.seat_state
+    }
+    fn new_capability(&mut self, _conn: &Connection, qh: &QueueHandle<Self>, seat: WlSeat, capability: Capability) {
+        if capability == Capability::Keyboard && self.keyboard.is_none() {
+            match self.seat_state.get_keyboard(qh, &seat, None) {
+                Ok(r) => self.keyboard = Some(r),
+                Err(e) => println!("failed to get the keyboard: {e}")
+            }
+        }
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            match self.seat_state.get_pointer(qh, &seat) {
+                Ok(r) => self.pointer = Some(r),
+                Err(e) => println!("failed to get the pointer: {e}")
+            }
+        }
+    }
+    fn remove_capability(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat, capability: Capability) {
+        if capability == Capability::Keyboard && let Some(keyboard) = self.keyboard.take() {
+            keyboard.release();
+        }
+        if capability == Capability::Pointer && let Some(pointer) = self.pointer.take() {
+            pointer.release();
+        }
+    }
+    fn new_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat) {}
+    fn remove_seat(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _seat: WlSeat) {}
+}
+
+impl KeyboardHandler for PreviewState {
+    fn press_key(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, event: KeyEvent) {
+        // shift steps a second at a time instead of a frame
+        let step = if self.shift { 60 } else { 1 };
+        match event.keysym {
+            Keysym::space => self.playback.paused = !self.playback.paused,
+            Keysym::Right | Keysym::period => self.playback.step(step),
+            Keysym::Left | Keysym::comma => self.playback.step(-step),
+            Keysym::Home => self.playback.position = 0.0,
+            Keysym::r => self.reload(),
+            Keysym::Escape | Keysym::q => self.close = true,
+            _ => return
+        }
+        self.update_title();
+    }
+    fn update_modifiers(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, modifiers: Modifiers, _raw_modifiers: RawModifiers, _layout: u32) {
+        self.shift = modifiers.shift;
+    }
+    fn enter(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _surface: &WlSurface, _serial: u32, _raw: &[u32], _keysyms: &[Keysym]) {}
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _surface: &WlSurface, _serial: u32) {}
+    fn repeat_key(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, _event: KeyEvent) {}
+    fn release_key(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _keyboard: &WlKeyboard, _serial: u32, _event: KeyEvent) {}
+}
+
+// Clicking or dragging along the bottom of the window moves through time.
+impl PointerHandler for PreviewState {
+    fn pointer_frame(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _pointer: &WlPointer, events: &[PointerEvent]) {
+        for event in events {
+            self.pointer_position = event.position;
+            match event.kind {
+                PointerEventKind::Press { .. } if event.position.1 >= self.size.1 as f64 - SCRUBBER_HEIGHT => {
+                    self.scrubbing = true;
+                    self.seek_to_pointer();
+                },
+                PointerEventKind::Motion { .. } if self.scrubbing => self.seek_to_pointer(),
+                PointerEventKind::Release { .. } | PointerEventKind::Leave { .. } => self.scrubbing = false,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl ProvidesRegistryState for PreviewState {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState, SeatState];
+}
+
+delegate_compositor!(PreviewState);
+delegate_output!(PreviewState);
+delegate_seat!(PreviewState);
+delegate_keyboard!(PreviewState);
+delegate_pointer!(PreviewState);
+delegate_xdg_shell!(PreviewState);
+delegate_xdg_window!(PreviewState);
+delegate_registry!(PreviewState);
+
+pub fn start(config: MonitorConfig, size: (u32, u32), loop_frames: u32) -> Result<(), String> {
+    let conn = Connection::connect_to_env().map_err(|e| format!("unable to connect to a compositor: {e}"))?;
+    let (globals, mut event_queue) = registry_queue_init(&conn).map_err(|e| e.to_string())?;
+    let qh = event_queue.handle();
+
+    let compositor = CompositorState::bind(&globals, &qh).map_err(|_| "compositor does not support 'wl_compositor'".to_string())?;
+    let xdg_shell = XdgShell::bind(&globals, &qh).map_err(|_| "compositor does not support 'xdg_wm_base'".to_string())?;
+    let window = xdg_shell.create_window(compositor.create_surface(&qh), WindowDecorations::RequestServer, &qh);
+    window.set_app_id("gpuburden-preview");
+    window.set_min_size(Some((160, 90)));
+    window.commit();
+
+    let mut state = PreviewState {
+        close: false,
+        config,
+        window,
+        renderer: None,
+        size,
+        scale: 1,
+        playback: Playback { position: 0.0, paused: false, length: loop_frames },
+        shift: false,
+        pointer_position: (0.0, 0.0),
+        scrubbing: false,
+        watched: Vec::new(),
+        last_reload_check: Instant::now(),
+        last_draw: Instant::now(),
+        keyboard: None,
+        pointer: None,
+
+        conn,
+        registry_state: RegistryState::new(&globals),
+        seat_state: SeatState::new(&globals, &qh),
+        output_state: OutputState::new(&globals, &qh)
+    };
+
+    while !state.close {
+        event_queue.blocking_dispatch(&mut state).map_err(|e| e.to_string())?;
+    }
+    if let Some(renderer) = state.renderer.take() {
+        renderer.free_surface();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Playback;
+
+    #[test]
+    fn playback_loops_and_steps() {
+        let mut playback = Playback { position: 0.0, paused: false, length: 120 };
+        playback.advance(Duration::from_secs(2));
+        playback.advance(Duration::from_millis(25));
+        assert_eq!(playback.frame(), 1);
+
+        playback.step(-2);
+        assert!(playback.paused);
+        assert_eq!(playback.frame(), 119);
+        playback.advance(Duration::from_secs(1));
+        assert_eq!(playback.frame(), 119);
+
+        playback.seek(0.5);
+        assert_eq!(playback.frame(), 60);
+        playback.seek(1.0);
+        assert_eq!(playback.frame(), 119);
+    }
+
+    #[test]
+    fn playback_keeps_real_time_on_fast_displays() {
+        let mut playback = Playback { position: 0.0, paused: false, length: 600 };
+        // a second of frame callbacks on a 144Hz display
+        for _ in 0..144 {
+            playback.advance(Duration::from_secs_f64(1.0 / 144.0));
+        }
+        assert!((59..=60).contains(&playback.frame()));
+    }
+}
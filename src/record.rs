@@ -0,0 +1,226 @@
+use std::{fs::{self, File}, io::{BufWriter, Seek, SeekFrom, Write}, path::PathBuf, process::{Command, Stdio}};
+
+use gif::{Encoder, Frame, Repeat};
+use image_webp::{ColorType, WebPEncoder};
+
+use crate::{cli::{RecordArgs, RecordFormat}, headless::{exit_with, shader_config, write_png}, renderer::Renderer};
+
+// Renders a fixed number of frames offscreen, stepping the frame counter by a fixed amount each
+// time, and hands them to one of the sinks below.
+pub fn record(args: &RecordArgs) {
+    let (width, height) = args.size;
+    if frame_number(args.start, args.step, args.frames.saturating_sub(1)).is_none() {
+        exit_with(&format!("frame numbers go past {} with --start {} --step {} --frames {}", u32::MAX, args.start, args.step, args.frames));
+    }
+    let config = shader_config(&args.shader, args.seed);
+    let mut renderer = match Renderer::headless(width, height, &config) {
+        Ok(r) => r,
+        Err(e) => exit_with(&e)
+    };
+
+    let mut sink = match open_sink(args) {
+        Ok(r) => r,
+        Err(e) => exit_with(&e)
+    };
+    for index in 0..args.frames {
+        // can't overflow since the last frame was checked above
+        let frame = args.start + index * args.step;
+        let pixels = renderer.render_frame(frame);
+        if let Err(e) = sink.write(index, &pixels) {
+            exit_with(&format!("failed to write frame {frame}: {e}"));
+        }
+        if (index + 1) % 60 == 0 || index + 1 == args.frames {
+            println!("recorded {}/{} frames", index + 1, args.frames);
+        }
+    }
+    if let Err(e) = sink.finish() {
+        exit_with(&format!("failed to finish recording: {e}"));
+    }
+}
+
+fn frame_number(start: u32, step: u32, index: u32) -> Option<u32> {
+    index.checked_mul(step)?.checked_add(start)
+}
+
+enum Sink {
+    PngSequence { directory: PathBuf, width: u32, height: u32 },
+    Gif { encoder: Encoder<BufWriter<File>>, width: u16, height: u16, delay: u16 },
+    // frames are written as they come, the file's size in the header is filled in at the end
+    WebP { file: BufWriter<File>, width: u32, height: u32, duration: u32 },
+    Pipe { child: std::process::Child }
+}
+
+fn open_sink(args: &RecordArgs) -> Result<Sink, String> {
+    let (width, height) = args.size;
+    if let Some(command) = &args.pipe {
+        let command = command
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string())
+            .replace("{fps}", &args.fps.to_string());
+        let child = Command::new("sh")
+            .args(["-c", &command])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to start `{command}`: {e}"))?;
+        return Ok(Sink::Pipe { child });
+    }
+
+    let output = args.output.clone().ok_or("either --output or --pipe is needed")?;
+    let format = match args.format {
+        Some(r) => r,
+        None => match output.extension().and_then(|e| e.to_str()) {
+            Some("gif") => RecordFormat::Gif,
+            Some("webp") => RecordFormat::Webp,
+            _ => RecordFormat::Png
+        }
+    };
+
+    match format {
+        RecordFormat::Png => {
+            fs::create_dir_all(&output).map_err(|e| format!("failed to create {}: {e}", output.display()))?;
+            Ok(Sink::PngSequence { directory: output, width, height })
+        },
+        RecordFormat::Gif => {
+            let (width, height) = (u16::try_from(width), u16::try_from(height));
+            let (Ok(width), Ok(height)) = (width, height) else {
+                return Err("gifs can't be bigger than 65535x65535".to_string());
+            };
+            let file = File::create(&output).map_err(|e| format!("failed to create {}: {e}", output.display()))?;
+            let mut encoder = Encoder::new(BufWriter::new(file), width, height, &[]).map_err(|e| e.to_string())?;
+            encoder.set_repeat(Repeat::Infinite).map_err(|e| e.to_string())?;
+            // gif delays are in hundredths of a second
+            let delay = (100.0 / args.fps).round().max(1.0) as u16;
+            Ok(Sink::Gif { encoder, width, height, delay })
+        },
+        RecordFormat::Webp => {
+            // frame durations are 24 bits
+            let duration = (1000.0 / args.fps).round().clamp(1.0, 0xffffff as f32) as u32;
+            let file = File::create(&output).map_err(|e| format!("failed to create {}: {e}", output.display()))?;
+            open_animated_webp(BufWriter::new(file), width, height, duration)
+        }
+    }
+}
+
+impl Sink {
+    fn write(&mut self, index: u32, pixels: &[u8]) -> Result<(), String> {
+        match self {
+            Sink::PngSequence { directory, width, height } => {
+                write_png(&directory.join(format!("frame-{index:05}.png")), *width, *height, pixels)
+            },
+            Sink::Gif { encoder, width, height, delay } => {
+                let mut pixels = pixels.to_vec();
+                let mut frame = Frame::from_rgba_speed(*width, *height, &mut pixels, 10);
+                frame.delay = *delay;
+                encoder.write_frame(&frame).map_err(|e| e.to_string())
+            },
+            Sink::WebP { file, width, height, duration } => {
+                let mut encoded = Vec::new();
+                WebPEncoder::new(&mut encoded).encode(pixels, *width, *height, ColorType::Rgba8).map_err(|e| e.to_string())?;
+                // a simple WebP file is "RIFF", size, "WEBP" and then the VP8L chunk
+                let vp8l = encoded.get(12..).filter(|chunk| chunk.starts_with(b"VP8L")).ok_or("unexpected webp encoder output")?;
+                let mut anmf = vec![0; 6]; // x and y offset
+                anmf.extend_from_slice(&(*width - 1).to_le_bytes()[..3]);
+                anmf.extend_from_slice(&(*height - 1).to_le_bytes()[..3]);
+                anmf.extend_from_slice(&duration.to_le_bytes()[..3]);
+                anmf.push(0x02); // don't blend with the previous frame
+                anmf.extend_from_slice(vp8l);
+                write_chunk(file, b"ANMF", &anmf).map_err(|e| e.to_string())
+            },
+            Sink::Pipe { child } => {
+                let stdin = child.stdin.as_mut().ok_or("encoder closed its input")?;
+                stdin.write_all(pixels).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            Sink::PngSequence { .. } => Ok(()),
+            Sink::Gif { encoder, .. } => {
+                encoder.into_inner().map_err(|e| e.to_string())?.flush().map_err(|e| e.to_string())
+            },
+            Sink::WebP { mut file, .. } => {
+                let length = file.stream_position().map_err(|e| e.to_string())?;
+                let size = u32::try_from(length - 8).map_err(|_| "recording is too big for a webp file, it's limited to 4GiB")?;
+                file.seek(SeekFrom::Start(4)).map_err(|e| e.to_string())?;
+                file.write_all(&size.to_le_bytes()).map_err(|e| e.to_string())?;
+                file.flush().map_err(|e| e.to_string())
+            },
+            Sink::Pipe { mut child } => {
+                drop(child.stdin.take());
+                let status = child.wait().map_err(|e| e.to_string())?;
+                if !status.success() {
+                    return Err(format!("encoder exited with {status}"));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// image-webp only writes still images, so each frame is encoded on its own and its VP8L chunk is
+// moved into an ANMF chunk. See https://developers.google.com/speed/webp/docs/riff_container
+fn open_animated_webp(mut file: BufWriter<File>, width: u32, height: u32, duration: u32) -> Result<Sink, String> {
+    let mut vp8x = vec![0x02 | 0x10, 0, 0, 0]; // animation and alpha flags
+    vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+
+    let mut anim = vec![0, 0, 0, 0]; // background colour
+    anim.extend_from_slice(&0u16.to_le_bytes()); // loop forever
+
+    // the size after RIFF is filled in once every frame is written
+    file.write_all(b"RIFF\0\0\0\0WEBP")
+        .and_then(|()| write_chunk(&mut file, b"VP8X", &vp8x))
+        .and_then(|()| write_chunk(&mut file, b"ANIM", &anim))
+        .map_err(|e| e.to_string())?;
+    Ok(Sink::WebP { file, width, height, duration })
+}
+
+fn write_chunk(output: &mut impl Write, name: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    output.write_all(name)?;
+    output.write_all(&(data.len() as u32).to_le_bytes())?;
+    output.write_all(data)?;
+    if data.len() % 2 == 1 {
+        output.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::{self, File}, io::{BufReader, BufWriter}};
+
+    use image_webp::WebPDecoder;
+
+    use super::{frame_number, open_animated_webp};
+
+    #[test]
+    fn frame_numbers_that_overflow_are_caught() {
+        assert_eq!(frame_number(10, 2, 5), Some(20));
+        assert_eq!(frame_number(u32::MAX - 1, 1, 1), Some(u32::MAX));
+        assert_eq!(frame_number(u32::MAX, 1, 1), None);
+        assert_eq!(frame_number(0, u32::MAX, 2), None);
+    }
+
+    #[test]
+    fn animated_webp_round_trips() {
+        let path = std::env::temp_dir().join(format!("gpuburden-record-test-{}.webp", std::process::id()));
+        let red = [255, 0, 0, 255].repeat(6);
+        let blue = [0, 0, 255, 128].repeat(6);
+        let mut sink = open_animated_webp(BufWriter::new(File::create(&path).unwrap()), 3, 2, 17).unwrap();
+        sink.write(0, &red).unwrap();
+        sink.write(1, &blue).unwrap();
+        sink.finish().unwrap();
+
+        let mut decoder = WebPDecoder::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.dimensions(), (3, 2));
+        assert_eq!(decoder.num_frames(), 2);
+        let mut pixels = vec![0; decoder.output_buffer_size().unwrap()];
+        assert_eq!(decoder.read_frame(&mut pixels).unwrap(), 17);
+        assert_eq!(pixels, red);
+        assert_eq!(decoder.read_frame(&mut pixels).unwrap(), 17);
+        assert_eq!(pixels, blue);
+        fs::remove_file(path).unwrap();
+    }
+}
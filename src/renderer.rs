@@ -1,20 +1,12 @@
-use std::{fs::File, io::Read, num::NonZero};
+use std::{mem, num::NonZero, path::Path, time::{Duration, Instant}};
 
 use bytemuck::NoUninit;
 use rand::{rngs::ThreadRng, Rng};
-use wgpu::{include_wgsl, rwh::{RawDisplayHandle, RawWindowHandle}, util::{BufferInitDescriptor, DeviceExt}, Adapter, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState, Buffer, ColorTargetState, ColorWrites, Device, Face, FragmentState, FrontFace, Instance, InstanceDescriptor, LoadOp, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceTargetUnsafe, TextureViewDescriptor};
+use wgpu::{include_wgsl, rwh::{RawDisplayHandle, RawWindowHandle}, util::{BufferInitDescriptor, DeviceExt, TextureDataOrder}, Adapter, AdapterInfo, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, CompositeAlphaMode, Device, DeviceDescriptor, ErrorFilter, Face, Features, FragmentState, FrontFace, Extent3d, Instance, InstanceDescriptor, LoadOp, MapMode, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor, PollType, PolygonMode, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceTargetUnsafe, TexelCopyBufferInfo, TexelCopyBufferLayout, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension};
 
-use crate::configuration::MonitorConfig;
+use crate::{configuration::MonitorConfig, font, preprocessor, timing::GpuTimer, uniforms::{self, CanvasBuffer, FragmentInputBuffer, OutputBuffer, PointerBuffer, UniformBlock}};
 
-const DEFAULT_SHADER: ShaderModuleDescriptor<'_> = include_wgsl!("../examples/default-shader.wgsl");
-
-#[repr(C)]
-#[derive(Copy, Clone, NoUninit)]
-struct FragmentInputBuffer {
-    screen_size: [u32; 2],
-    frame: u32,
-    seed: u32
-}
+const DEFAULT_SHADER: &str = include_str!("../examples/default-shader.wgsl");
 
 // Most of the rendering code is based off of https://github.com/Smithay/client-toolkit/blob/master/examples/wgpu.rs
 // I couldn't find examples on how to re-use the wgpu instance/device to render to two different
@@ -22,22 +14,95 @@ struct FragmentInputBuffer {
 // If that exists, please link me there because I understand this way of doing things where there's
 // an entirely different GPU instance for each surface is terrible.
 pub struct Renderer {
-    surface: Surface<'static>,
-    surface_config: Option<SurfaceConfiguration>,
+    target: RenderTarget,
+    format: Option<TextureFormat>,
     adapter: Adapter,
     device: Device,
     queue: Queue,
-    fragment_buffer: Option<Buffer>,
+    uniform_blocks: Vec<&'static UniformBlock>,
+    uniform_buffers: Vec<Buffer>, // in the same order as uniform_blocks
     bind_group: Option<BindGroup>,
     pipeline: Option<RenderPipeline>,
     shader_code: ShaderModuleDescriptor<'static>,
+    // path of the shader, for error messages
+    shader_name: String,
 
     pub width: u32,
     pub height: u32,
-    surface_configured: bool,
+    // wl_output transform of the output, passed to the shader
+    pub transform: u32,
+    pub span: Option<SpanPlacement>,
+    // what the canvas time counts from, without one it's worked out from the frame number so
+    // offscreen renders come out the same every time
+    pub time_base: Option<Instant>,
+    // kept up to date by the wayland code, the transform is filled in from `transform`
+    pub output: OutputBuffer,
+    pub pointer: PointerBuffer,
+    // the shader's alpha is blended over whatever is below the surface instead of replacing it
+    transparent: bool,
+    // drawn on top of the shader when set, for `gpuburden lock`
+    pub prompt: Option<PromptBuffer>,
+    prompt_overlay: Option<Overlay>,
+    // the time bar at the bottom of `gpuburden preview`
+    pub scrubber: Option<ScrubberBuffer>,
+    scrubber_overlay: Option<Overlay>,
+    // shader errors and the like, drawn in the top left corner
+    overlay_text: Option<String>,
+    text_overlay: Option<Overlay>,
+    // put shader errors on the output as well as printing them
+    show_errors: bool,
+    configured: bool,
     frame: u32,
-    rand: ThreadRng
+    seed: u32,
+    rand: ThreadRng,
+    timer: Option<GpuTimer>
+}
+// The password prompt, see shaders/prompt.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Default, NoUninit)]
+pub struct PromptBuffer {
+    pub screen_size: [u32; 2],
+    pub length: u32,
+    pub state: u32,
+    pub scale: f32,
+    pub _padding: u32
 }
+
+// See shaders/scrubber.wgsl.
+#[repr(C)]
+#[derive(Copy, Clone, Default, NoUninit)]
+pub struct ScrubberBuffer {
+    pub screen_size: [u32; 2],
+    pub progress: f32,
+    pub scale: f32,
+    pub paused: u32,
+    pub _padding: u32
+}
+
+// A built-in shader drawn over the user's one, with a single binding at 0.
+struct Overlay {
+    pipeline: RenderPipeline,
+    bind_group: BindGroup,
+    // for the overlays that take a uniform
+    buffer: Option<Buffer>
+}
+
+// Where an output sits in the bounding box of its span, in the output's own pixels.
+#[derive(Clone, Copy, PartialEq)]
+pub struct SpanPlacement {
+    pub offset: [u32; 2],
+    pub size: [u32; 2]
+}
+
+enum RenderTarget {
+    Surface(Surface<'static>),
+    // used when rendering without a compositor, the texture is read back after every frame
+    Texture(Texture)
+}
+
+// sRGB so the bytes read back can go straight into a PNG
+const HEADLESS_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
 impl Renderer {
     pub fn for_layer(raw_display_handle: RawDisplayHandle, raw_window_handle: RawWindowHandle, config: &Option<MonitorConfig>) -> Self {
         let instance = Instance::new(&InstanceDescriptor {
@@ -58,107 +123,266 @@ impl Renderer {
             ..Default::default()
         })).expect("Wgpu failed to find a compatible adapter.");
 
-        let (device, queue) = pollster::block_on(adapter.request_device(&Default::default())).expect("Failed to request a wgpu device.");
-
-        let mut shader_code: ShaderModuleDescriptor = DEFAULT_SHADER;
-        if let Some(config) = config {
-            // read from the file 
-            if config.shader.trim() != "default" {
-                match File::open(&config.shader) {
-                    Ok(mut file) => {
-                        let mut contents = String::new();
-                        match file.read_to_string(&mut contents) {
-                            Ok(_) => shader_code = ShaderModuleDescriptor { 
-                                label: None,
-                                source: wgpu::ShaderSource::Wgsl(contents.into())
-                            },
-                            Err(e) => println!("failed to read {}: {e}", config.shader),
-                        };
-                    },
-                    Err(e) => println!("shader file {} not found: {e}", config.shader)
-                };
-            }
+        let (device, queue) = Self::request_device(&adapter);
+        let (shader_code, error) = match config.as_ref().map(Self::load_shader) {
+            Some(Ok(r)) => (r, None),
+            Some(Err(e)) => (Self::default_shader(), Some(e)),
+            None => (Self::default_shader(), None)
+        };
+        let mut renderer = Self::new(RenderTarget::Surface(surface), adapter, device, queue, shader_code, config);
+        renderer.time_base = Some(Instant::now());
+        if let Some(e) = error {
+            renderer.shader_failed(e);
+        }
+        renderer
+    }
+
+    // Renders into a texture instead of a wayland surface, picking the adapter from the usual
+    // WGPU_BACKEND/WGPU_ADAPTER_NAME environment variables so a software adapter can be forced.
+    pub fn headless(width: u32, height: u32, config: &MonitorConfig) -> Result<Self, String> {
+        let shader_code = Self::load_shader(config)?;
+        let instance = Instance::new(&InstanceDescriptor::from_env_or_default());
+        let adapter = pollster::block_on(wgpu::util::initialize_adapter_from_env_or_default(&instance, None))
+            .map_err(|e| format!("wgpu failed to find an adapter: {e}"))?;
+        println!("rendering with {} ({:?})", adapter.get_info().name, adapter.get_info().backend);
+
+        let (device, queue) = Self::request_device(&adapter);
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: HEADLESS_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[]
+        });
+
+        let mut renderer = Self::new(RenderTarget::Texture(texture), adapter, device, queue, shader_code, &Some(config.clone()));
+        renderer.width = width;
+        renderer.height = height;
+        renderer.format = Some(HEADLESS_FORMAT);
+        renderer.build_pipeline()?;
+        renderer.configured = true;
+        Ok(renderer)
+    }
+
+    fn new(target: RenderTarget, adapter: Adapter, device: Device, queue: Queue, shader_code: ShaderModuleDescriptor<'static>, config: &Option<MonitorConfig>) -> Self {
+
+        let mut rand = rand::rng();
+        let seed = match config.as_ref().and_then(|config| config.seed) {
+            Some(r) => r,
+            None => rand.random_range(0..1000000)
         };
 
         Self {
-            surface,
-            surface_config: None,
+            target,
+            format: None,
             adapter,
             device,
             queue,
-            fragment_buffer: None,
+            uniform_blocks: uniforms::blocks(config.as_ref().is_some_and(|config| config.pointer)),
+            uniform_buffers: Vec::new(),
             bind_group: None,
             pipeline: None,
             shader_code,
+            shader_name: config.as_ref().map(|config| config.shader.clone()).unwrap_or_else(|| "default".to_string()),
 
             width: 0,
             height: 0,
-            surface_configured: false,
+            transform: 0,
+            span: None,
+            time_base: None,
+            output: OutputBuffer { scale: 1.0, ..Default::default() },
+            pointer: PointerBuffer::default(),
+            transparent: config.as_ref().is_some_and(|config| config.transparent),
+            prompt: None,
+            prompt_overlay: None,
+            scrubber: None,
+            scrubber_overlay: None,
+            overlay_text: None,
+            text_overlay: None,
+            show_errors: config.as_ref().and_then(|config| config.show_errors).unwrap_or(false),
+            configured: false,
             frame: 0,
-            rand: rand::rng()
+            seed,
+            rand,
+            timer: None
+        }
+    }
+
+    fn request_device(adapter: &Adapter) -> (Device, Queue) {
+        // timestamps are only used for optional timing, so only ask for them when they're there
+        let descriptor = DeviceDescriptor {
+            required_features: adapter.features() & Features::TIMESTAMP_QUERY,
+            ..Default::default()
+        };
+        pollster::block_on(adapter.request_device(&descriptor)).expect("Failed to request a wgpu device.")
+    }
+
+    fn default_shader() -> ShaderModuleDescriptor<'static> {
+        let mut shader = preprocessor::preprocess_source("default-shader.wgsl", DEFAULT_SHADER, &[]).expect("Failed to preprocess the built-in default shader.");
+        uniforms::inject_declarations(&mut shader, false).expect("Built-in default shader failed to compile. Bug report this!");
+        ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(shader.source.into())
+        }
+    }
+
+    fn load_shader(config: &MonitorConfig) -> Result<ShaderModuleDescriptor<'static>, String> {
+        if config.shader.trim() == "default" {
+            return Ok(Self::default_shader());
+        }
+
+        let mut shader = preprocessor::preprocess(Path::new(&config.shader), &config.include_paths)
+            .map_err(|e| format!("failed to load shader {}: {e}", config.shader))?;
+        uniforms::inject_declarations(&mut shader, config.pointer)
+            .map_err(|e| format!("shader {} failed to compile.\n{e}", config.shader))?;
+        Ok(ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(shader.source.into())
+        })
+    }
+
+    // Swaps in a new version of the shader, the old one keeps running if it doesn't compile.
+    pub fn reload_shader(&mut self, config: &MonitorConfig) -> Result<(), String> {
+        let shader_code = Self::load_shader(config)?;
+        let previous_code = mem::replace(&mut self.shader_code, shader_code);
+        let previous_name = mem::replace(&mut self.shader_name, config.shader.clone());
+        if self.configured && let Err(e) = self.build_pipeline() {
+            self.shader_code = previous_code;
+            self.shader_name = previous_name;
+            self.reconfigure_pipeline();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    // Prints why the shader can't be used, and shows it on the output too if the config asked for that.
+    fn shader_failed(&mut self, error: String) {
+        println!("{error}\nusing the default shader.");
+        if self.show_errors {
+            self.set_overlay_text(Some(error));
         }
     }
 
     pub fn configure_surface(&mut self, width: u32, height: u32) {
-        let surface_capabilities = self.surface.get_capabilities(&self.adapter);
+        let RenderTarget::Surface(surface) = &self.target else {
+            panic!("configure_surface called on a headless renderer. Bug report this!");
+        };
+        let surface_capabilities = surface.get_capabilities(&self.adapter);
+        let alpha_mode = match self.transparent {
+            true if surface_capabilities.alpha_modes.contains(&CompositeAlphaMode::PreMultiplied) => CompositeAlphaMode::PreMultiplied,
+            true => {
+                println!("the surface doesn't support premultiplied alpha, it won't be transparent.");
+                CompositeAlphaMode::Auto
+            },
+            false => CompositeAlphaMode::Auto
+        };
         let surface_config = SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_capabilities.formats[0],
             view_formats: vec![surface_capabilities.formats[0]],
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            alpha_mode,
             width,
             height,
             desired_maximum_frame_latency: 2,
             present_mode: wgpu::PresentMode::AutoVsync
         };
-        self.surface.configure(&self.device, &surface_config);
-        self.surface_config = Some(surface_config);
+        surface.configure(&self.device, &surface_config);
+        self.format = Some(surface_config.format);
+        self.width = width;
+        self.height = height;
         self.reconfigure_pipeline();
-        self.surface_configured = true;
+        self.configured = true;
+    }
+
+    fn fragment_input(&mut self) -> FragmentInputBuffer {
+        FragmentInputBuffer {
+            screen_size: [self.width, self.height],
+            frame: self.frame,
+            seed: self.seed,
+            frame_random: self.rand.random_range(0..1000000),
+            ..Default::default()
+        }
     }
 
+    fn canvas(&self) -> CanvasBuffer {
+        let (offset, size) = match self.span {
+            Some(r) => (r.offset, r.size),
+            None => ([0, 0], [self.width, self.height])
+        };
+        CanvasBuffer { offset, size, time: self.time(), ..Default::default() }
+    }
+
+    // Seconds on the canvas clock.
+    pub fn time(&self) -> f32 {
+        match self.time_base {
+            Some(r) => r.elapsed().as_secs_f32(),
+            None => self.frame as f32 / 60.0
+        }
+    }
+
+    // The contents of every uniform buffer this frame, in the same order as uniform_blocks.
+    fn uniform_contents(&mut self) -> Vec<Vec<u8>> {
+        self.uniform_blocks.clone().iter().map(|block| match block.binding {
+            0 => bytemuck::bytes_of(&self.fragment_input()).to_vec(),
+            1 => bytemuck::bytes_of(&self.canvas()).to_vec(),
+            2 => bytemuck::bytes_of(&OutputBuffer { transform: self.transform, ..self.output }).to_vec(),
+            3 => bytemuck::bytes_of(&self.pointer).to_vec(),
+            _ => unreachable!("No contents for uniform binding {}. Bug report this!", block.binding)
+        }).collect()
+    }
+
+    // Shaders that naga accepts can still be turned down by wgpu when making the pipeline, like
+    // when fs_main is missing, so those fall back to the default shader instead of crashing.
     fn reconfigure_pipeline(&mut self) {
+        if let Err(e) = self.build_pipeline() {
+            self.shader_code = Self::default_shader();
+            self.shader_name = "default".to_string();
+            self.shader_failed(e);
+            self.build_pipeline().expect("Built-in default shader failed to compile. Bug report this!");
+        }
+    }
+
+    fn build_pipeline(&mut self) -> Result<(), String> {
+        self.device.push_error_scope(ErrorFilter::Validation);
         // Credit for teaching me this part goes to https://sotrh.github.io/learn-wgpu/beginner/tutorial3-pipeline
         let vertex_shader = self.device.create_shader_module(include_wgsl!("shaders/vertex.wgsl"));
         let fragment_shader = self.device.create_shader_module(self.shader_code.clone());
 
         // deal with the buffers first
-        let fragment_input_buffer = FragmentInputBuffer {
-            screen_size: [self.width, self.height],
-            frame: self.frame,
-            seed: self.rand.random_range(0..1000000)
-        };
-        let wgpu_fragment_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: bytemuck::cast_slice(&[fragment_input_buffer]),
-            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
-        });
+        let uniform_buffers: Vec<Buffer> = self.uniform_contents().iter().map(|contents| {
+            self.device.create_buffer_init(&BufferInitDescriptor {
+                label: None,
+                contents,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::UNIFORM,
+            })
+        }).collect();
         // bind groups 
         // thanks to the wgpu matrix server for making me realize these can pass into to the fragment shader
+        let layout_entries: Vec<BindGroupLayoutEntry> = self.uniform_blocks.iter().map(|block| BindGroupLayoutEntry {
+            binding: block.binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer { 
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZero::new(block.size as u64)
+            },
+            count: None
+        }).collect();
         let wgpu_bind_group_layout = self.device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer { 
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: NonZero::new(std::mem::size_of::<FragmentInputBuffer>() as u64)
-                    },
-                    count: None
-                }
-            ],
+            entries: &layout_entries,
             label: None
         });
+        let bind_group_entries: Vec<BindGroupEntry> = self.uniform_blocks.iter().zip(&uniform_buffers).map(|(block, buffer)| BindGroupEntry {
+            binding: block.binding,
+            resource: buffer.as_entire_binding(),
+        }).collect();
         let wgpu_bind_group = self.device.create_bind_group(&BindGroupDescriptor {
             label: None,
             layout: &wgpu_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu_fragment_buffer.as_entire_binding(),
-                },
-            ],
+            entries: &bind_group_entries,
         });
 
         // pipeline now
@@ -181,8 +405,9 @@ impl Renderer {
                 module: &fragment_shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(ColorTargetState {
-                    format: self.surface_config.clone().expect("Pipeline called to reconfigure without a surface config being set.").format,
-                    blend: Some(BlendState::REPLACE),
+                    format: self.format.expect("Pipeline called to reconfigure without a texture format being set."),
+                    // blending onto the transparent clear colour leaves premultiplied alpha behind
+                    blend: Some(if self.transparent { BlendState::ALPHA_BLENDING } else { BlendState::REPLACE }),
                     write_mask: ColorWrites::ALL
                 })],
                 compilation_options: PipelineCompilationOptions::default()
@@ -205,35 +430,201 @@ impl Renderer {
             multiview: None,
             cache: None
         });
+        if let Some(e) = pollster::block_on(self.device.pop_error_scope()) {
+            return Err(format!("shader {} failed to compile.\n{e}", self.shader_name));
+        }
 
-        self.fragment_buffer = Some(wgpu_fragment_buffer);
+        self.uniform_buffers = uniform_buffers;
         self.bind_group = Some(wgpu_bind_group);
         self.pipeline = Some(wgpu_pipeline);
+        if self.prompt.is_some() {
+            self.prompt_overlay = Some(self.uniform_overlay(include_wgsl!("shaders/prompt.wgsl"), size_of::<PromptBuffer>() as u64));
+        }
+        if self.scrubber.is_some() {
+            self.scrubber_overlay = Some(self.uniform_overlay(include_wgsl!("shaders/scrubber.wgsl"), size_of::<ScrubberBuffer>() as u64));
+        }
+        self.text_overlay = self.overlay_text.as_deref().map(|text| self.text_overlay(text));
+        Ok(())
+    }
+
+    // Shows the text over the shader until it's set back to None.
+    pub fn set_overlay_text(&mut self, text: Option<String>) {
+        if text == self.overlay_text {
+            return;
+        }
+        self.overlay_text = text;
+        if self.configured {
+            self.text_overlay = self.overlay_text.as_deref().map(|text| self.text_overlay(text));
+        }
+    }
+
+    fn uniform_overlay(&self, shader: ShaderModuleDescriptor, size: u64) -> Overlay {
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            mapped_at_creation: false
+        });
+        let binding = BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None
+        };
+        let mut overlay = self.create_overlay(shader, binding, buffer.as_entire_binding());
+        overlay.buffer = Some(buffer);
+        overlay
+    }
+
+    fn text_overlay(&self, text: &str) -> Overlay {
+        // has to match MARGIN in shaders/text.wgsl
+        const MARGIN: u32 = 16;
+        let scale = self.output.scale.round().max(1.0) as u32;
+        let image = font::render_text(text, scale, (self.width.saturating_sub(MARGIN * 2), self.height.saturating_sub(MARGIN * 2)));
+        let texture = self.device.create_texture_with_data(&self.queue, &TextureDescriptor {
+            label: None,
+            size: Extent3d { width: image.width.max(1), height: image.height.max(1), depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING,
+            view_formats: &[]
+        }, TextureDataOrder::LayerMajor, &image.pixels);
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let binding = BindingType::Texture {
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+            multisampled: false
+        };
+        self.create_overlay(include_wgsl!("shaders/text.wgsl"), binding, BindingResource::TextureView(&view))
+    }
+
+    fn create_overlay(&self, shader: ShaderModuleDescriptor, binding: BindingType, resource: BindingResource) -> Overlay {
+        let vertex_shader = self.device.create_shader_module(include_wgsl!("shaders/vertex.wgsl"));
+        let fragment_shader = self.device.create_shader_module(shader);
+        let bind_group_layout = self.device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: binding,
+                count: None
+            }],
+            label: None
+        });
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry { binding: 0, resource }]
+        });
+        let pipeline_layout = self.device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[]
+        });
+        let pipeline = self.device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vertex_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default()
+            },
+            fragment: Some(FragmentState {
+                module: &fragment_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(ColorTargetState {
+                    format: self.format.expect("Overlay created without a texture format being set."),
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: ColorWrites::ALL
+                })],
+                compilation_options: PipelineCompilationOptions::default()
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache: None
+        });
+        Overlay { pipeline, bind_group, buffer: None }
     }
 
     pub fn draw(&mut self) {
-        let texture = self.surface.get_current_texture().expect("Failed to get swapchain texture (is your GPU too busy?)");
+        self.draw_frame(self.frame + 1);
+    }
+
+    // Draws a particular frame, for when something else is keeping time.
+    pub fn draw_frame(&mut self, frame: u32) {
+        let RenderTarget::Surface(surface) = &self.target else {
+            panic!("draw called on a headless renderer. Bug report this!");
+        };
+        let texture = surface.get_current_texture().expect("Failed to get swapchain texture (is your GPU too busy?)");
         let texture_view = texture.texture.create_view(&TextureViewDescriptor::default());
-        self.frame += 1;
+        self.frame = frame;
+        self.render(&texture_view);
+        texture.present();
+    }
+
+    // Renders the given frame number and returns it as tightly packed RGBA8 rows.
+    pub fn render_frame(&mut self, frame: u32) -> Vec<u8> {
+        let texture = self.render_offscreen(frame);
+        self.read_texture(&texture)
+    }
+
+    // Renders the given frame number without reading it back, returning the texture it went to.
+    pub fn render_offscreen(&mut self, frame: u32) -> Texture {
+        let RenderTarget::Texture(texture) = &self.target else {
+            panic!("render_offscreen called on a surface renderer. Bug report this!");
+        };
+        let texture = texture.clone();
+        self.frame = frame;
+        self.render(&texture.create_view(&TextureViewDescriptor::default()));
+        texture
+    }
+
+    // Starts timing render passes on the GPU, returns false if the adapter can't.
+    pub fn enable_gpu_timing(&mut self) -> bool {
+        self.timer = GpuTimer::new(&self.device, &self.queue);
+        self.timer.is_some()
+    }
+    pub fn disable_gpu_timing(&mut self) {
+        self.timer = None;
+    }
+
+    // The GPU time of the last timed frame, if it has come back yet.
+    pub fn take_gpu_time(&mut self, wait: bool) -> Option<Duration> {
+        self.timer.as_mut()?.take(&self.device, wait)
+    }
+
+    // Blocks until the GPU has finished everything submitted so far.
+    pub fn wait_idle(&self) {
+        self.device.poll(PollType::wait_indefinitely()).expect("Failed waiting for the GPU to finish rendering.");
+    }
+
+    pub fn adapter_info(&self) -> AdapterInfo {
+        self.adapter.get_info()
+    }
+
+    fn render(&mut self, texture_view: &TextureView) {
         let mut encoder = self.device.create_command_encoder(&Default::default());
         {
             let mut renderpass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &texture_view,
+                    view: texture_view,
                     resolve_target: None,
                     depth_slice: None,
                     ops: wgpu::Operations {
-                        load: LoadOp::Clear(wgpu::Color::BLUE),
+                        load: LoadOp::Clear(if self.transparent { wgpu::Color::TRANSPARENT } else { wgpu::Color::BLUE }),
                         store: StoreOp::Store
                     }
                 })],
                 depth_stencil_attachment: None,
-                timestamp_writes: None,
+                timestamp_writes: self.timer.as_mut().and_then(|timer| timer.timestamp_writes()),
                 occlusion_query_set: None
             });
 
-            if self.surface_configured {
+            if self.configured {
                 let pipeline = self.pipeline.as_ref().expect("WGPU was configured but pipeline not set. Bug report this!");
                 renderpass.set_pipeline(pipeline);
                 renderpass.set_bind_group(0, &self.bind_group, &[]);
@@ -241,21 +632,95 @@ impl Renderer {
             }
         }
 
-        if self.surface_configured {
-            let frag_buffer = self.fragment_buffer.as_ref().expect("WGPU was configured but fragment input buffer not set. Bug report this!");
-            let fragment_input_buffer = FragmentInputBuffer {
-                screen_size: [self.width, self.height],
-                frame: self.frame,
-                seed: self.rand.random_range(0..1000000)
-            };
-            self.queue.write_buffer(frag_buffer, 0, bytemuck::cast_slice(&[fragment_input_buffer]));
+        if let (Some(prompt), Some(Overlay { buffer: Some(buffer), .. })) = (self.prompt, &self.prompt_overlay) {
+            let prompt = PromptBuffer { screen_size: [self.width, self.height], ..prompt };
+            self.queue.write_buffer(buffer, 0, bytemuck::bytes_of(&prompt));
+        }
+        if let (Some(scrubber), Some(Overlay { buffer: Some(buffer), .. })) = (self.scrubber, &self.scrubber_overlay) {
+            let scrubber = ScrubberBuffer { screen_size: [self.width, self.height], ..scrubber };
+            self.queue.write_buffer(buffer, 0, bytemuck::bytes_of(&scrubber));
+        }
+        let overlays: Vec<&Overlay> = [&self.prompt_overlay, &self.scrubber_overlay, &self.text_overlay].into_iter().flatten().collect();
+        if !overlays.is_empty() {
+            let mut renderpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: texture_view,
+                    resolve_target: None,
+                    depth_slice: None,
+                    ops: wgpu::Operations {
+                        load: LoadOp::Load,
+                        store: StoreOp::Store
+                    }
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None
+            });
+            for overlay in overlays {
+                renderpass.set_pipeline(&overlay.pipeline);
+                renderpass.set_bind_group(0, &overlay.bind_group, &[]);
+                renderpass.draw(0..3, 0..1);
+            }
+        }
+
+        if self.configured {
+            let uniform_contents = self.uniform_contents();
+            for (buffer, contents) in self.uniform_buffers.iter().zip(uniform_contents) {
+                self.queue.write_buffer(buffer, 0, &contents);
+            }
         }
 
+        if let Some(timer) = &mut self.timer {
+            timer.resolve(&mut encoder);
+        }
         self.queue.submit(Some(encoder.finish()));
-        texture.present();
+        if let Some(timer) = &mut self.timer {
+            timer.submitted();
+        }
+    }
+
+    fn read_texture(&self, texture: &Texture) -> Vec<u8> {
+        // rows in the copy have to be padded out to COPY_BYTES_PER_ROW_ALIGNMENT
+        let unpadded_row = self.width * 4;
+        let padded_row = unpadded_row.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = self.device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (padded_row * self.height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: None
+                }
+            },
+            Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 }
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(MapMode::Read, |result| result.expect("Failed to map the readback buffer."));
+        self.wait_idle();
+
+        let mapped = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((unpadded_row * self.height) as usize);
+        for row in mapped.chunks(padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row as usize]);
+        }
+        drop(mapped);
+        buffer.unmap();
+        pixels
     }
 
     pub fn free_surface(self) {
-        drop(self.surface);
+        drop(self.target);
     }
 }
@@ -0,0 +1,65 @@
+// Colour space conversions
+// All colours are in the 0-1 range.
+fn gpuburden_srgb_to_linear(srgb: vec3f) -> vec3f {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3f(2.4));
+    return select(high, low, srgb <= vec3f(0.04045));
+}
+fn gpuburden_linear_to_srgb(linear: vec3f) -> vec3f {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3f(1. / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3f(0.0031308));
+}
+
+// OKLab, https://bottosson.github.io/posts/oklab/
+// Expects and returns *linear* rgb.
+fn gpuburden_linear_to_oklab(c: vec3f) -> vec3f {
+    let l = 0.4122214708 * c.r + 0.5363325363 * c.g + 0.0514459929 * c.b;
+    let m = 0.2119034982 * c.r + 0.6806995451 * c.g + 0.1073969566 * c.b;
+    let s = 0.0883024619 * c.r + 0.2817188376 * c.g + 0.6299787005 * c.b;
+
+    let lms = sign(vec3f(l, m, s)) * pow(abs(vec3f(l, m, s)), vec3f(1. / 3.));
+
+    return vec3f(
+        0.2104542553 * lms.x + 0.7936177850 * lms.y - 0.0040720468 * lms.z,
+        1.9779984951 * lms.x - 2.4285922050 * lms.y + 0.4505937099 * lms.z,
+        0.0259040371 * lms.x + 0.7827717662 * lms.y - 0.8086757660 * lms.z
+    );
+}
+fn gpuburden_oklab_to_linear(c: vec3f) -> vec3f {
+    let l_ = c.x + 0.3963377774 * c.y + 0.2158037573 * c.z;
+    let m_ = c.x - 0.1055613458 * c.y - 0.0638541728 * c.z;
+    let s_ = c.x - 0.0894841775 * c.y - 1.2914855480 * c.z;
+
+    let l = l_ * l_ * l_;
+    let m = m_ * m_ * m_;
+    let s = s_ * s_ * s_;
+
+    return vec3f(
+        4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s
+    );
+}
+fn gpuburden_srgb_to_oklab(c: vec3f) -> vec3f { return gpuburden_linear_to_oklab(gpuburden_srgb_to_linear(c)); }
+fn gpuburden_oklab_to_srgb(c: vec3f) -> vec3f { return gpuburden_linear_to_srgb(gpuburden_oklab_to_linear(c)); }
+// Mixes two srgb colours in OKLab, which avoids the muddy middle you get mixing in rgb.
+fn gpuburden_oklab_mix(a: vec3f, b: vec3f, t: f32) -> vec3f {
+    return gpuburden_oklab_to_srgb(mix(gpuburden_srgb_to_oklab(a), gpuburden_srgb_to_oklab(b), t));
+}
+
+// HSV, hue/saturation/value all in 0-1.
+// https://web.archive.org/web/20200207113336/http://lolengine.net/blog/2013/07/27/rgb-to-hsv-in-glsl
+fn gpuburden_rgb_to_hsv(c: vec3f) -> vec3f {
+    let k = vec4f(0., -1. / 3., 2. / 3., -1.);
+    let p = mix(vec4f(c.bg, k.wz), vec4f(c.gb, k.xy), step(c.b, c.g));
+    let q = mix(vec4f(p.xyw, c.r), vec4f(c.r, p.yzx), step(p.x, c.r));
+    let d = q.x - min(q.w, q.y);
+    let e = 1.0e-10;
+    return vec3f(abs(q.z + (q.w - q.y) / (6. * d + e)), d / (q.x + e), q.x);
+}
+fn gpuburden_hsv_to_rgb(c: vec3f) -> vec3f {
+    let k = vec4f(1., 2. / 3., 1. / 3., 3.);
+    let p = abs(fract(c.xxx + k.xyz) * 6. - k.www);
+    return c.z * mix(k.xxx, clamp(p - k.xxx, vec3f(0.), vec3f(1.)), c.y);
+}
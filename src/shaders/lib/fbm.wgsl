@@ -0,0 +1,25 @@
+// Fractal brownian motion, layering octaves of perlin noise.
+#import noise
+
+fn gpuburden_fbm2(p: vec2f, octaves: i32) -> f32 {
+    var value = 0.;
+    var amplitude = 0.5;
+    var position = p;
+    for (var i = 0; i < octaves; i++) {
+        value += gpuburden_perlin_noise2(position) * amplitude;
+        position *= 2.;
+        amplitude *= 0.5;
+    }
+    return value;
+}
+fn gpuburden_fbm3(p: vec3f, octaves: i32) -> f32 {
+    var value = 0.;
+    var amplitude = 0.5;
+    var position = p;
+    for (var i = 0; i < octaves; i++) {
+        value += gpuburden_perlin_noise3(position) * amplitude;
+        position *= 2.;
+        amplitude *= 0.5;
+    }
+    return value;
+}
@@ -0,0 +1,37 @@
+// Hash functions
+// PCG based integer hashes from "Hash Functions for GPU Rendering", Jarzynski & Olano 2020.
+// The float variants hash the bit pattern of their input and return values in [0, 1].
+fn gpuburden_pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+fn gpuburden_pcg2d(v: vec2u) -> vec2u {
+    var x = v * 1664525u + 1013904223u;
+    x.x += x.y * 1664525u;
+    x.y += x.x * 1664525u;
+    x = x ^ (x >> vec2u(16u));
+    x.x += x.y * 1664525u;
+    x.y += x.x * 1664525u;
+    x = x ^ (x >> vec2u(16u));
+    return x;
+}
+fn gpuburden_pcg3d(v: vec3u) -> vec3u {
+    var x = v * 1664525u + 1013904223u;
+    x.x += x.y * x.z;
+    x.y += x.z * x.x;
+    x.z += x.x * x.y;
+    x = x ^ (x >> vec3u(16u));
+    x.x += x.y * x.z;
+    x.y += x.z * x.x;
+    x.z += x.x * x.y;
+    return x;
+}
+
+const GPUBURDEN_HASH_SCALE: f32 = 1.0 / 4294967295.0;
+
+fn gpuburden_hash11(p: f32) -> f32 { return f32(gpuburden_pcg(bitcast<u32>(p))) * GPUBURDEN_HASH_SCALE; }
+fn gpuburden_hash21(p: vec2f) -> f32 { return f32(gpuburden_pcg2d(bitcast<vec2u>(p)).x) * GPUBURDEN_HASH_SCALE; }
+fn gpuburden_hash22(p: vec2f) -> vec2f { return vec2f(gpuburden_pcg2d(bitcast<vec2u>(p))) * GPUBURDEN_HASH_SCALE; }
+fn gpuburden_hash31(p: vec3f) -> f32 { return f32(gpuburden_pcg3d(bitcast<vec3u>(p)).x) * GPUBURDEN_HASH_SCALE; }
+fn gpuburden_hash33(p: vec3f) -> vec3f { return vec3f(gpuburden_pcg3d(bitcast<vec3u>(p))) * GPUBURDEN_HASH_SCALE; }
@@ -0,0 +1,41 @@
+// Constants, remapping and rotation helpers
+const GPUBURDEN_PI: f32 = 3.14159265358979;
+const GPUBURDEN_TAU: f32 = 6.28318530717959;
+
+fn gpuburden_remap(value: f32, in_min: f32, in_max: f32, out_min: f32, out_max: f32) -> f32 {
+    return out_min + (value - in_min) / (in_max - in_min) * (out_max - out_min);
+}
+
+// Rotates counter-clockwise by `angle` radians, use as `gpuburden_rotate2d(a) * p`.
+fn gpuburden_rotate2d(angle: f32) -> mat2x2f {
+    let c = cos(angle);
+    let s = sin(angle);
+    return mat2x2f(c, s, -s, c);
+}
+fn gpuburden_rotate_x(angle: f32) -> mat3x3f {
+    let c = cos(angle);
+    let s = sin(angle);
+    return mat3x3f(1., 0., 0., 0., c, s, 0., -s, c);
+}
+fn gpuburden_rotate_y(angle: f32) -> mat3x3f {
+    let c = cos(angle);
+    let s = sin(angle);
+    return mat3x3f(c, 0., -s, 0., 1., 0., s, 0., c);
+}
+fn gpuburden_rotate_z(angle: f32) -> mat3x3f {
+    let c = cos(angle);
+    let s = sin(angle);
+    return mat3x3f(c, s, 0., -s, c, 0., 0., 0., 1.);
+}
+// Rotation around an arbitrary (normalized) axis, Rodrigues' formula.
+fn gpuburden_rotate_axis(axis: vec3f, angle: f32) -> mat3x3f {
+    let c = cos(angle);
+    let s = sin(angle);
+    let t = 1. - c;
+    let a = axis;
+    return mat3x3f(
+        t * a.x * a.x + c,       t * a.x * a.y + s * a.z, t * a.x * a.z - s * a.y,
+        t * a.x * a.y - s * a.z, t * a.y * a.y + c,       t * a.y * a.z + s * a.x,
+        t * a.x * a.z + s * a.y, t * a.y * a.z - s * a.x, t * a.z * a.z + c
+    );
+}
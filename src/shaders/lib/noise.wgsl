@@ -0,0 +1,261 @@
+// Noise functions
+// Perlin and simplex noise return roughly -1 to 1, value and worley noise return 0 to 1.
+#import hash
+
+// Perlin Noise
+// https://gist.github.com/munrocket/236ed5ba7e409b8bdf1ff6eca5dcdc39
+// MIT License. © Stefan Gustavson, Munrocket
+fn gpuburden_permute4(x: vec4f) -> vec4f { return ((x * 34. + 1.) * x) % vec4f(289.); }
+fn gpuburden_taylorInvSqrt4(r: vec4f) -> vec4f { return 1.79284291400159 - 0.85373472095314 * r; }
+fn gpuburden_fade2(t: vec2f) -> vec2f { return t * t * t * (t * (t * 6. - 15.) + 10.); }
+fn gpuburden_fade3(t: vec3f) -> vec3f { return t * t * t * (t * (t * 6. - 15.) + 10.); }
+
+fn gpuburden_perlin_noise2(P: vec2f) -> f32 {
+    var Pi: vec4f = floor(P.xyxy) + vec4f(0., 0., 1., 1.);
+    let Pf = fract(P.xyxy) - vec4f(0., 0., 1., 1.);
+    Pi = Pi % vec4f(289.); // To avoid truncation effects in permutation
+    let ix = Pi.xzxz;
+    let iy = Pi.yyww;
+    let fx = Pf.xzxz;
+    let fy = Pf.yyww;
+    let i = gpuburden_permute4(gpuburden_permute4(ix) + iy);
+    var gx: vec4f = 2. * fract(i * 0.0243902439) - 1.; // 1/41 = 0.024...
+    let gy = abs(gx) - 0.5;
+    let tx = floor(gx + 0.5);
+    gx = gx - tx;
+    var g00: vec2f = vec2f(gx.x, gy.x);
+    var g10: vec2f = vec2f(gx.y, gy.y);
+    var g01: vec2f = vec2f(gx.z, gy.z);
+    var g11: vec2f = vec2f(gx.w, gy.w);
+    let norm = 1.79284291400159 - 0.85373472095314 *
+        vec4f(dot(g00, g00), dot(g01, g01), dot(g10, g10), dot(g11, g11));
+    g00 = g00 * norm.x;
+    g01 = g01 * norm.y;
+    g10 = g10 * norm.z;
+    g11 = g11 * norm.w;
+    let n00 = dot(g00, vec2f(fx.x, fy.x));
+    let n10 = dot(g10, vec2f(fx.y, fy.y));
+    let n01 = dot(g01, vec2f(fx.z, fy.z));
+    let n11 = dot(g11, vec2f(fx.w, fy.w));
+    let fade_xy = gpuburden_fade2(Pf.xy);
+    let n_x = mix(vec2f(n00, n01), vec2f(n10, n11), vec2f(fade_xy.x));
+    let n_xy = mix(n_x.x, n_x.y, fade_xy.y);
+    return 2.3 * n_xy;
+}
+fn gpuburden_perlin_noise3(P: vec3f) -> f32 {
+    var Pi0 : vec3f = floor(P); // Integer part for indexing
+    var Pi1 : vec3f = Pi0 + vec3f(1.); // Integer part + 1
+    Pi0 = Pi0 % vec3f(289.);
+    Pi1 = Pi1 % vec3f(289.);
+    let Pf0 = fract(P); // Fractional part for interpolation
+    let Pf1 = Pf0 - vec3f(1.); // Fractional part - 1.
+    let ix = vec4f(Pi0.x, Pi1.x, Pi0.x, Pi1.x);
+    let iy = vec4f(Pi0.yy, Pi1.yy);
+    let iz0 = Pi0.zzzz;
+    let iz1 = Pi1.zzzz;
+
+    let ixy = gpuburden_permute4(gpuburden_permute4(ix) + iy);
+    let ixy0 = gpuburden_permute4(ixy + iz0);
+    let ixy1 = gpuburden_permute4(ixy + iz1);
+
+    var gx0: vec4f = ixy0 / 7.;
+    var gy0: vec4f = fract(floor(gx0) / 7.) - 0.5;
+    gx0 = fract(gx0);
+    var gz0: vec4f = vec4f(0.5) - abs(gx0) - abs(gy0);
+    var sz0: vec4f = step(gz0, vec4f(0.));
+    gx0 = gx0 + sz0 * (step(vec4f(0.), gx0) - 0.5);
+    gy0 = gy0 + sz0 * (step(vec4f(0.), gy0) - 0.5);
+
+    var gx1: vec4f = ixy1 / 7.;
+    var gy1: vec4f = fract(floor(gx1) / 7.) - 0.5;
+    gx1 = fract(gx1);
+    var gz1: vec4f = vec4f(0.5) - abs(gx1) - abs(gy1);
+    var sz1: vec4f = step(gz1, vec4f(0.));
+    gx1 = gx1 - sz1 * (step(vec4f(0.), gx1) - 0.5);
+    gy1 = gy1 - sz1 * (step(vec4f(0.), gy1) - 0.5);
+
+    var g000: vec3f = vec3f(gx0.x, gy0.x, gz0.x);
+    var g100: vec3f = vec3f(gx0.y, gy0.y, gz0.y);
+    var g010: vec3f = vec3f(gx0.z, gy0.z, gz0.z);
+    var g110: vec3f = vec3f(gx0.w, gy0.w, gz0.w);
+    var g001: vec3f = vec3f(gx1.x, gy1.x, gz1.x);
+    var g101: vec3f = vec3f(gx1.y, gy1.y, gz1.y);
+    var g011: vec3f = vec3f(gx1.z, gy1.z, gz1.z);
+    var g111: vec3f = vec3f(gx1.w, gy1.w, gz1.w);
+
+    let norm0 = gpuburden_taylorInvSqrt4(
+        vec4f(dot(g000, g000), dot(g010, g010), dot(g100, g100), dot(g110, g110)));
+    g000 = g000 * norm0.x;
+    g010 = g010 * norm0.y;
+    g100 = g100 * norm0.z;
+    g110 = g110 * norm0.w;
+    let norm1 = gpuburden_taylorInvSqrt4(
+        vec4f(dot(g001, g001), dot(g011, g011), dot(g101, g101), dot(g111, g111)));
+    g001 = g001 * norm1.x;
+    g011 = g011 * norm1.y;
+    g101 = g101 * norm1.z;
+    g111 = g111 * norm1.w;
+
+    let n000 = dot(g000, Pf0);
+    let n100 = dot(g100, vec3f(Pf1.x, Pf0.yz));
+    let n010 = dot(g010, vec3f(Pf0.x, Pf1.y, Pf0.z));
+    let n110 = dot(g110, vec3f(Pf1.xy, Pf0.z));
+    let n001 = dot(g001, vec3f(Pf0.xy, Pf1.z));
+    let n101 = dot(g101, vec3f(Pf1.x, Pf0.y, Pf1.z));
+    let n011 = dot(g011, vec3f(Pf0.x, Pf1.yz));
+    let n111 = dot(g111, Pf1);
+
+    var fade_xyz: vec3f = gpuburden_fade3(Pf0);
+    let temp = vec4f(f32(fade_xyz.z)); // simplify after chrome bug fix
+    let n_z = mix(vec4f(n000, n100, n010, n110), vec4f(n001, n101, n011, n111), temp);
+    let n_yz = mix(n_z.xy, n_z.zw, vec2f(f32(fade_xyz.y))); // simplify after chrome bug fix
+    let n_xyz = mix(n_yz.x, n_yz.y, fade_xyz.x);
+    return 2.2 * n_xyz;
+}
+
+fn gpuburden_mod289_3(x: vec3f) -> vec3f { return x - floor(x / 289.) * 289.; }
+fn gpuburden_permute3(x: vec3f) -> vec3f { return gpuburden_mod289_3((x * 34. + 1.) * x); }
+
+// Simplex Noise
+// https://github.com/ashima/webgl-noise
+// MIT License. © Ian McEwan, Stefan Gustavson, Ashima Arts
+fn gpuburden_simplex_noise2(v: vec2f) -> f32 {
+    let C = vec4f(0.211324865405187, 0.366025403784439, -0.577350269189626, 0.024390243902439);
+    var i: vec2f = floor(v + dot(v, C.yy));
+    let x0 = v - i + dot(i, C.xx);
+    var i1 = vec2f(0., 1.);
+    if x0.x > x0.y {
+        i1 = vec2f(1., 0.);
+    }
+    var x12: vec4f = x0.xyxy + C.xxzz;
+    x12 = vec4f(x12.xy - i1, x12.zw);
+    i = i - floor(i / 289.) * 289.;
+    let p = gpuburden_permute3(gpuburden_permute3(i.y + vec3f(0., i1.y, 1.)) + i.x + vec3f(0., i1.x, 1.));
+    var m: vec3f = max(0.5 - vec3f(dot(x0, x0), dot(x12.xy, x12.xy), dot(x12.zw, x12.zw)), vec3f(0.));
+    m = m * m;
+    m = m * m;
+    let x = 2. * fract(p * C.www) - 1.;
+    let h = abs(x) - 0.5;
+    let ox = floor(x + 0.5);
+    let a0 = x - ox;
+    m = m * (1.79284291400159 - 0.85373472095314 * (a0 * a0 + h * h));
+    let g = vec3f(a0.x * x0.x + h.x * x0.y, a0.yz * x12.xz + h.yz * x12.yw);
+    return 130. * dot(m, g);
+}
+fn gpuburden_simplex_noise3(v: vec3f) -> f32 {
+    let C = vec2f(1. / 6., 1. / 3.);
+    let D = vec4f(0., 0.5, 1., 2.);
+
+    var i: vec3f = floor(v + dot(v, C.yyy));
+    let x0 = v - i + dot(i, C.xxx);
+
+    let g = step(x0.yzx, x0.xyz);
+    let l = 1. - g;
+    let i1 = min(g.xyz, l.zxy);
+    let i2 = max(g.xyz, l.zxy);
+
+    let x1 = x0 - i1 + C.xxx;
+    let x2 = x0 - i2 + C.yyy;
+    let x3 = x0 - D.yyy;
+
+    i = gpuburden_mod289_3(i);
+    let p = gpuburden_permute4(gpuburden_permute4(gpuburden_permute4(
+        i.z + vec4f(0., i1.z, i2.z, 1.))
+        + i.y + vec4f(0., i1.y, i2.y, 1.))
+        + i.x + vec4f(0., i1.x, i2.x, 1.));
+
+    let n_ = 0.142857142857; // 1/7
+    let ns = n_ * D.wyz - D.xzx;
+
+    let j = p - 49. * floor(p * ns.z * ns.z);
+    let x_ = floor(j * ns.z);
+    let y_ = floor(j - 7. * x_);
+
+    let x = x_ * ns.x + ns.yyyy;
+    let y = y_ * ns.x + ns.yyyy;
+    let h = 1. - abs(x) - abs(y);
+
+    let b0 = vec4f(x.xy, y.xy);
+    let b1 = vec4f(x.zw, y.zw);
+
+    let s0 = floor(b0) * 2. + 1.;
+    let s1 = floor(b1) * 2. + 1.;
+    let sh = -step(h, vec4f(0.));
+
+    let a0 = b0.xzyw + s0.xzyw * sh.xxyy;
+    let a1 = b1.xzyw + s1.xzyw * sh.zzww;
+
+    var p0: vec3f = vec3f(a0.xy, h.x);
+    var p1: vec3f = vec3f(a0.zw, h.y);
+    var p2: vec3f = vec3f(a1.xy, h.z);
+    var p3: vec3f = vec3f(a1.zw, h.w);
+
+    let norm = gpuburden_taylorInvSqrt4(vec4f(dot(p0, p0), dot(p1, p1), dot(p2, p2), dot(p3, p3)));
+    p0 = p0 * norm.x;
+    p1 = p1 * norm.y;
+    p2 = p2 * norm.z;
+    p3 = p3 * norm.w;
+
+    var m: vec4f = max(0.6 - vec4f(dot(x0, x0), dot(x1, x1), dot(x2, x2), dot(x3, x3)), vec4f(0.));
+    m = m * m;
+    return 42. * dot(m * m, vec4f(dot(p0, x0), dot(p1, x1), dot(p2, x2), dot(p3, x3)));
+}
+
+// Value Noise
+fn gpuburden_value_noise2(p: vec2f) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = gpuburden_fade2(f);
+    let a = gpuburden_hash21(i);
+    let b = gpuburden_hash21(i + vec2f(1., 0.));
+    let c = gpuburden_hash21(i + vec2f(0., 1.));
+    let d = gpuburden_hash21(i + vec2f(1., 1.));
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+fn gpuburden_value_noise3(p: vec3f) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = gpuburden_fade3(f);
+    let n000 = gpuburden_hash31(i);
+    let n100 = gpuburden_hash31(i + vec3f(1., 0., 0.));
+    let n010 = gpuburden_hash31(i + vec3f(0., 1., 0.));
+    let n110 = gpuburden_hash31(i + vec3f(1., 1., 0.));
+    let n001 = gpuburden_hash31(i + vec3f(0., 0., 1.));
+    let n101 = gpuburden_hash31(i + vec3f(1., 0., 1.));
+    let n011 = gpuburden_hash31(i + vec3f(0., 1., 1.));
+    let n111 = gpuburden_hash31(i + vec3f(1., 1., 1.));
+    let z0 = mix(mix(n000, n100, u.x), mix(n010, n110, u.x), u.y);
+    let z1 = mix(mix(n001, n101, u.x), mix(n011, n111, u.x), u.y);
+    return mix(z0, z1, u.z);
+}
+
+// Worley (cellular) Noise
+// Returns the distance to the closest feature point.
+fn gpuburden_worley_noise2(p: vec2f) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    var closest = 8.;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2f(f32(x), f32(y));
+            let point = gpuburden_hash22(cell + offset);
+            closest = min(closest, length(offset + point - f));
+        }
+    }
+    return min(closest, 1.);
+}
+fn gpuburden_worley_noise3(p: vec3f) -> f32 {
+    let cell = floor(p);
+    let f = fract(p);
+    var closest = 8.;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let offset = vec3f(f32(x), f32(y), f32(z));
+                let point = gpuburden_hash33(cell + offset);
+                closest = min(closest, length(offset + point - f));
+            }
+        }
+    }
+    return min(closest, 1.);
+}
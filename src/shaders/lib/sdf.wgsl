@@ -0,0 +1,37 @@
+// Signed distance functions
+// Mostly from https://iquilezles.org/articles/distfunctions2d/ and https://iquilezles.org/articles/distfunctions/
+fn gpuburden_sd_circle(p: vec2f, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+fn gpuburden_sd_box(p: vec2f, half_size: vec2f) -> f32 {
+    let d = abs(p) - half_size;
+    return length(max(d, vec2f(0.))) + min(max(d.x, d.y), 0.);
+}
+fn gpuburden_sd_rounded_box(p: vec2f, half_size: vec2f, radius: f32) -> f32 {
+    return gpuburden_sd_box(p, half_size - vec2f(radius)) - radius;
+}
+fn gpuburden_sd_segment(p: vec2f, a: vec2f, b: vec2f) -> f32 {
+    let pa = p - a;
+    let ba = b - a;
+    let h = clamp(dot(pa, ba) / dot(ba, ba), 0., 1.);
+    return length(pa - ba * h);
+}
+fn gpuburden_sd_sphere(p: vec3f, radius: f32) -> f32 {
+    return length(p) - radius;
+}
+fn gpuburden_sd_box3(p: vec3f, half_size: vec3f) -> f32 {
+    let q = abs(p) - half_size;
+    return length(max(q, vec3f(0.))) + min(max(q.x, max(q.y, q.z)), 0.);
+}
+fn gpuburden_sd_torus(p: vec3f, radii: vec2f) -> f32 {
+    let q = vec2f(length(p.xz) - radii.x, p.y);
+    return length(q) - radii.y;
+}
+
+fn gpuburden_op_union(a: f32, b: f32) -> f32 { return min(a, b); }
+fn gpuburden_op_subtract(a: f32, b: f32) -> f32 { return max(a, -b); }
+fn gpuburden_op_intersect(a: f32, b: f32) -> f32 { return max(a, b); }
+fn gpuburden_op_smooth_union(a: f32, b: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (b - a) / k, 0., 1.);
+    return mix(b, a, h) - k * h * (1. - h);
+}
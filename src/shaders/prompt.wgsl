@@ -0,0 +1,51 @@
+// Password prompt drawn over the shader by `gpuburden lock`
+struct Prompt {
+    screen_size: vec2<u32>,
+    length: u32,
+    // 0 is waiting for a password, 1 is checking it and 2 is when the last one was wrong
+    state: u32,
+    scale: f32
+};
+@group(0) @binding(0) var<uniform> prompt: Prompt;
+
+const BOX_SIZE: vec2<f32> = vec2<f32>(360.0, 64.0);
+const DOT_RADIUS: f32 = 6.0;
+const DOT_SPACING: f32 = 20.0;
+
+// distance to a rounded box centred on the origin
+fn rounded_box(p: vec2<f32>, half_size: vec2<f32>, radius: f32) -> f32 {
+    let q = abs(p) - half_size + radius;
+    return length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+}
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let centre = vec2<f32>(prompt.screen_size) / 2.0;
+    let p = (pos.xy - centre) / prompt.scale;
+
+    let box_distance = rounded_box(p, BOX_SIZE / 2.0, 16.0);
+    if box_distance > 1.0 {
+        return vec4<f32>(0.0);
+    }
+
+    var border = vec3<f32>(0.9, 0.9, 0.9);
+    if prompt.state == 1u {
+        border = vec3<f32>(0.95, 0.75, 0.2);
+    } else if prompt.state == 2u {
+        border = vec3<f32>(0.9, 0.2, 0.2);
+    }
+
+    let max_dots = u32((BOX_SIZE.x - 32.0) / DOT_SPACING);
+    let dots = min(prompt.length, max_dots);
+    let first_dot = -f32(dots - 1u) * DOT_SPACING / 2.0;
+    var dot_distance = 1000.0;
+    for (var i = 0u; i < dots; i++) {
+        dot_distance = min(dot_distance, length(p - vec2<f32>(first_dot + f32(i) * DOT_SPACING, 0.0)) - DOT_RADIUS);
+    }
+
+    var color = vec4<f32>(0.05, 0.05, 0.05, 0.85);
+    color = mix(color, vec4<f32>(border, 1.0), clamp(1.5 - abs(box_distance + 2.0), 0.0, 1.0));
+    color = mix(color, vec4<f32>(0.9, 0.9, 0.9, 1.0), clamp(0.5 - dot_distance, 0.0, 1.0));
+    color.a *= clamp(0.5 - box_distance, 0.0, 1.0);
+    return color;
+}
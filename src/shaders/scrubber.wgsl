@@ -0,0 +1,35 @@
+// Time bar along the bottom of `gpuburden preview`
+struct Scrubber {
+    screen_size: vec2<u32>,
+    // how far through the loop, from 0 to 1
+    progress: f32,
+    scale: f32,
+    paused: u32
+};
+@group(0) @binding(0) var<uniform> scrubber: Scrubber;
+
+// has to match SCRUBBER_HEIGHT in preview.rs
+const HEIGHT: f32 = 12.0;
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(scrubber.screen_size);
+    let height = HEIGHT * scrubber.scale;
+    if pos.y < size.y - height {
+        return vec4<f32>(0.0);
+    }
+
+    let played = size.x * scrubber.progress;
+    var color = vec4<f32>(0.0, 0.0, 0.0, 0.6);
+    if pos.x < played {
+        color = vec4<f32>(0.85, 0.85, 0.85, 0.8);
+        if scrubber.paused == 1u {
+            color = vec4<f32>(0.95, 0.75, 0.2, 0.9);
+        }
+    }
+    // the playhead
+    if abs(pos.x - played) < 1.5 * scrubber.scale {
+        color = vec4<f32>(1.0);
+    }
+    return color;
+}
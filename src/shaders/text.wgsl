@@ -0,0 +1,14 @@
+// Text drawn by the renderer over the shader, already laid out into a texture by font.rs
+@group(0) @binding(0) var text: texture_2d<f32>;
+
+const MARGIN: i32 = 16;
+
+@fragment
+fn fs_main(@builtin(position) pos: vec4<f32>) -> @location(0) vec4<f32> {
+    let p = vec2<i32>(pos.xy) - vec2<i32>(MARGIN);
+    let size = vec2<i32>(textureDimensions(text));
+    if p.x < 0 || p.y < 0 || p.x >= size.x || p.y >= size.y {
+        return vec4<f32>(0.0);
+    }
+    return textureLoad(text, p, 0);
+}
@@ -0,0 +1,282 @@
+use std::{collections::BTreeMap, env, fs::{self, DirBuilder}, io::{ErrorKind, Read, Write}, os::unix::{fs::{DirBuilderExt, MetadataExt}, net::{UnixListener, UnixStream}}, path::{Path, PathBuf}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+
+use crate::sys;
+
+const WINDOW: Duration = Duration::from_secs(1);
+// A gap this long means the compositor stopped asking for frames, like when the output is covered
+// or asleep, rather than frames being missed.
+const IDLE_GAP: Duration = Duration::from_millis(500);
+
+// Per-output frame statistics, averaged over one second windows.
+pub struct OutputStats {
+    name: String,
+    shader: String,
+    expected_interval: Option<Duration>,
+
+    frames: u64,
+    dropped: u64,
+    last_frame: Option<Instant>,
+
+    window_start: Instant,
+    window_frames: u32,
+    window_cpu: Duration,
+    window_gpu: Duration,
+    window_gpu_frames: u32
+}
+
+#[derive(Clone)]
+pub struct StatsSnapshot {
+    pub name: String,
+    pub shader: String,
+    pub frames: u64,
+    pub fps: f64,
+    pub cpu_time: Duration,
+    pub gpu_time: Option<Duration>,
+    pub dropped: u64
+}
+
+impl OutputStats {
+    // refresh_rate is in mHz, like wl_output reports it
+    pub fn new(name: &str, shader: &str, refresh_rate: Option<i32>) -> Self {
+        Self {
+            name: name.to_string(),
+            shader: shader.to_string(),
+            expected_interval: refresh_rate.filter(|r| *r > 0).map(|r| Duration::from_secs_f64(1000.0 / r as f64)),
+
+            frames: 0,
+            dropped: 0,
+            last_frame: None,
+
+            window_start: Instant::now(),
+            window_frames: 0,
+            window_cpu: Duration::ZERO,
+            window_gpu: Duration::ZERO,
+            window_gpu_frames: 0
+        }
+    }
+
+    // Returns a snapshot whenever a window has finished. after_callback is whether the frame was
+    // drawn for the frame callback the previous one asked for, other frames don't say anything
+    // about missed refreshes.
+    pub fn record_frame(&mut self, now: Instant, cpu_time: Duration, gpu_time: Option<Duration>, after_callback: bool) -> Option<StatsSnapshot> {
+        if let Some(last_frame) = self.last_frame && after_callback {
+            let interval = now - last_frame;
+            if interval >= IDLE_GAP {
+                // the window would otherwise count the idle time against the frame rate
+                self.start_window(now);
+            } else if let Some(expected) = self.expected_interval && interval > expected.mul_f32(1.5) {
+                // anything that took more than one and a half refreshes to come around again missed at least one
+                self.dropped += (interval.as_secs_f64() / expected.as_secs_f64()).round() as u64 - 1;
+            }
+        }
+        self.last_frame = Some(now);
+
+        self.frames += 1;
+        self.window_frames += 1;
+        self.window_cpu += cpu_time;
+        if let Some(gpu_time) = gpu_time {
+            self.window_gpu += gpu_time;
+            self.window_gpu_frames += 1;
+        }
+
+        let elapsed = now - self.window_start;
+        if elapsed < WINDOW {
+            return None;
+        }
+        let snapshot = StatsSnapshot {
+            name: self.name.clone(),
+            shader: self.shader.clone(),
+            frames: self.frames,
+            fps: self.window_frames as f64 / elapsed.as_secs_f64(),
+            cpu_time: self.window_cpu / self.window_frames,
+            gpu_time: (self.window_gpu_frames > 0).then(|| self.window_gpu / self.window_gpu_frames),
+            dropped: self.dropped
+        };
+
+        self.start_window(now);
+        Some(snapshot)
+    }
+
+    fn start_window(&mut self, now: Instant) {
+        self.window_start = now;
+        self.window_frames = 0;
+        self.window_cpu = Duration::ZERO;
+        self.window_gpu = Duration::ZERO;
+        self.window_gpu_frames = 0;
+    }
+}
+
+// Latest snapshot for every output, shared with the thread answering `gpuburden stats`.
+#[derive(Clone, Default)]
+pub struct StatsRegistry {
+    snapshots: Arc<Mutex<BTreeMap<String, StatsSnapshot>>>
+}
+
+impl StatsRegistry {
+    pub fn publish(&self, snapshot: StatsSnapshot) {
+        self.snapshots.lock().expect("Stats lock poisoned.").insert(snapshot.name.clone(), snapshot);
+    }
+    pub fn remove(&self, name: &str) {
+        self.snapshots.lock().expect("Stats lock poisoned.").remove(name);
+    }
+
+    pub fn report(&self) -> String {
+        let snapshots = self.snapshots.lock().expect("Stats lock poisoned.");
+        if snapshots.is_empty() {
+            return "no outputs are being drawn yet.\n".to_string();
+        }
+
+        let mut report = format!("{:<12} {:>7} {:>10} {:>8} {:>8} {:>8}  shader\n", "output", "fps", "frames", "cpu ms", "gpu ms", "dropped");
+        for snapshot in snapshots.values() {
+            let gpu_time = match snapshot.gpu_time {
+                Some(r) => format!("{:.3}", r.as_secs_f64() * 1000.0),
+                None => "-".to_string()
+            };
+            report.push_str(&format!(
+                "{:<12} {:>7.1} {:>10} {:>8.3} {:>8} {:>8}  {}\n",
+                snapshot.name, snapshot.fps, snapshot.frames, snapshot.cpu_time.as_secs_f64() * 1000.0, gpu_time, snapshot.dropped, snapshot.shader
+            ));
+        }
+        report
+    }
+
+    // Answers every connection to the stats socket with the current report. Fails when another
+    // daemon is already answering on it, anything else only means `gpuburden stats` won't work.
+    pub fn serve(&self) -> Result<(), String> {
+        let path = socket_path();
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!("gpuburden is already running, it's answering on {}.", path.display()));
+        }
+        if runtime_directory().is_none() && let Err(e) = private_directory(&socket_directory()) {
+            println!("can't open the stats socket, `gpuburden stats` won't work: {e}");
+            return Ok(());
+        }
+        // left behind by a daemon that didn't get to clean up
+        let _ = fs::remove_file(&path);
+        let listener = match UnixListener::bind(&path) {
+            Ok(r) => r,
+            Err(e) => {
+                println!("failed to open the stats socket at {}, `gpuburden stats` won't work: {e}", path.display());
+                return Ok(());
+            }
+        };
+
+        let registry = self.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let _ = stream.write_all(registry.report().as_bytes());
+            }
+        });
+        Ok(())
+    }
+}
+
+pub fn socket_path() -> PathBuf {
+    socket_directory().join("gpuburden.sock")
+}
+
+// $XDG_RUNTIME_DIR is already private to the user, without it the socket goes in a folder of its
+// own in /tmp so other users can't take its place.
+fn socket_directory() -> PathBuf {
+    match runtime_directory() {
+        Some(r) => r,
+        None => env::temp_dir().join(format!("gpuburden-{}", sys::uid()))
+    }
+}
+
+fn runtime_directory() -> Option<PathBuf> {
+    env::var_os("XDG_RUNTIME_DIR").filter(|directory| !directory.is_empty()).map(PathBuf::from)
+}
+
+// Makes a folder only we can get into if it isn't there, and checks nobody else made it first.
+fn private_directory(directory: &Path) -> Result<(), String> {
+    match DirBuilder::new().mode(0o700).create(directory) {
+        Ok(()) => {},
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {},
+        Err(e) => return Err(format!("failed to create {}: {e}", directory.display()))
+    }
+    let metadata = fs::symlink_metadata(directory).map_err(|e| format!("failed to check {}: {e}", directory.display()))?;
+    let uid = sys::uid();
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(format!("{} isn't a folder owned by you", directory.display()));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        return Err(format!("{} can be opened by other users", directory.display()));
+    }
+    Ok(())
+}
+
+// Used by `gpuburden stats` to ask the running daemon for its report.
+pub fn query() -> Result<String, String> {
+    let path = socket_path();
+    let mut stream = UnixStream::connect(&path).map_err(|e| format!("couldn't connect to {} (is gpuburden running?): {e}", path.display()))?;
+    let mut report = String::new();
+    stream.read_to_string(&mut report).map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::PermissionsExt, time::{Duration, Instant}};
+
+    use super::OutputStats;
+
+    const FRAME: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn counts_missed_refreshes() {
+        // 100 Hz
+        let mut stats = OutputStats::new("DP-2", "default", Some(100000));
+        let start = Instant::now();
+        stats.record_frame(start, Duration::ZERO, None, false);
+        stats.record_frame(start + FRAME, Duration::ZERO, None, true);
+        assert_eq!(stats.dropped, 0);
+        // two refreshes went by without a frame
+        stats.record_frame(start + FRAME * 4, Duration::ZERO, None, true);
+        assert_eq!(stats.dropped, 2);
+        // frames the compositor didn't ask for don't say anything about missed ones
+        stats.record_frame(start + FRAME * 10, Duration::ZERO, None, false);
+        assert_eq!(stats.dropped, 2);
+    }
+
+    #[test]
+    fn idle_gaps_are_not_dropped_frames() {
+        let mut stats = OutputStats::new("DP-2", "default", Some(100000));
+        let start = Instant::now();
+        stats.record_frame(start, Duration::ZERO, None, false);
+        // covered by a fullscreen window for a while
+        stats.record_frame(start + Duration::from_secs(30), Duration::ZERO, None, true);
+        assert_eq!(stats.dropped, 0);
+        assert_eq!(stats.window_frames, 1);
+        assert_eq!(stats.window_start, start + Duration::from_secs(30));
+    }
+
+    #[test]
+    fn private_directory_refuses_shared_folders() {
+        let directory = std::env::temp_dir().join(format!("gpuburden-private-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        super::private_directory(&directory).expect("a new folder should be private");
+        assert_eq!(std::fs::metadata(&directory).unwrap().permissions().mode() & 0o777, 0o700);
+
+        std::fs::set_permissions(&directory, std::fs::Permissions::from_mode(0o755)).unwrap();
+        assert!(super::private_directory(&directory).is_err());
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn averages_over_each_window() {
+        let mut stats = OutputStats::new("DP-2", "default", Some(100000));
+        let start = stats.window_start;
+        let mut snapshot = None;
+        for frame in 0..=100 {
+            let gpu_time = (frame % 2 == 0).then_some(Duration::from_millis(4));
+            snapshot = stats.record_frame(start + FRAME * frame, Duration::from_millis(2), gpu_time, frame > 0).or(snapshot);
+        }
+        let snapshot = snapshot.expect("a window should have finished after a second");
+        assert_eq!(snapshot.frames, 101);
+        assert!((snapshot.fps - 101.0).abs() < 0.01, "fps was {}", snapshot.fps);
+        assert_eq!(snapshot.cpu_time, Duration::from_millis(2));
+        assert_eq!(snapshot.gpu_time, Some(Duration::from_millis(4)));
+        assert_eq!(stats.window_frames, 0);
+    }
+}
@@ -0,0 +1,62 @@
+// WGSL modules that ship inside the binary, shaders can pull these in by name, e.g. `#import noise`.
+const MODULES: &[(&str, &str)] = &[
+    ("hash", include_str!("shaders/lib/hash.wgsl")),
+    ("noise", include_str!("shaders/lib/noise.wgsl")),
+    ("fbm", include_str!("shaders/lib/fbm.wgsl")),
+    ("color", include_str!("shaders/lib/color.wgsl")),
+    ("sdf", include_str!("shaders/lib/sdf.wgsl")),
+    ("math", include_str!("shaders/lib/math.wgsl")),
+];
+
+pub fn module(name: &str) -> Option<&'static str> {
+    MODULES.iter().find(|(module_name, _)| *module_name == name).map(|(_, source)| *source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MODULES;
+    use crate::{preprocessor, uniforms};
+
+    #[test]
+    fn modules_validate() {
+        for (name, _) in MODULES {
+            let shader = preprocessor::preprocess_source("test.wgsl", &format!("#import {name}\n"), &[])
+                .unwrap_or_else(|e| panic!("failed to preprocess {name}: {e}"));
+            if let Err(e) = shader.validate() {
+                panic!("module {name} failed to validate:\n{e}");
+            }
+        }
+    }
+
+    #[test]
+    fn modules_validate_together() {
+        let source: String = MODULES.iter().map(|(name, _)| format!("#import {name}\n")).collect();
+        let shader = preprocessor::preprocess_source("test.wgsl", &source, &[]).expect("failed to preprocess");
+        if let Err(e) = shader.validate() {
+            panic!("modules conflict with each other:\n{e}");
+        }
+    }
+
+    #[test]
+    fn modules_leave_common_names_free() {
+        let mut source: String = MODULES.iter().map(|(name, _)| format!("#import {name}\n")).collect();
+        source.push_str("const PI: f32 = 3.14;\nfn hash(p: vec2f) -> f32 { return p.x; }\nfn noise(p: vec2f) -> f32 { return p.y; }\nfn remap(v: f32) -> f32 { return v; }\nfn fade2(t: vec2f) -> vec2f { return t; }\n");
+        let shader = preprocessor::preprocess_source("test.wgsl", &source, &[]).expect("failed to preprocess");
+        if let Err(e) = shader.validate() {
+            panic!("modules clash with names a shader is likely to use:\n{e}");
+        }
+    }
+
+    #[test]
+    fn examples_validate() {
+        for (file, source) in [
+            ("default-shader.wgsl", include_str!("../examples/default-shader.wgsl")),
+            ("distorted-noise.wgsl", include_str!("../examples/distorted-noise.wgsl")),
+        ] {
+            let mut shader = preprocessor::preprocess_source(file, source, &[]).expect("failed to preprocess");
+            if let Err(e) = uniforms::inject_declarations(&mut shader, false) {
+                panic!("{file} failed to validate:\n{e}");
+            }
+        }
+    }
+}
@@ -0,0 +1,10 @@
+// The few bits of libc that std doesn't wrap.
+
+unsafe extern "C" {
+    fn getuid() -> u32;
+}
+
+// The real user id gpuburden is running as.
+pub fn uid() -> u32 {
+    unsafe { getuid() }
+}
@@ -0,0 +1,118 @@
+use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Duration};
+
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Device, Features, MapMode, PollType, QuerySet, QuerySetDescriptor, QueryType, Queue, RenderPassTimestampWrites};
+
+// Measures how long the GPU spends on a render pass with timestamp queries.
+// Results come back asynchronously, so only one measurement is in flight at a time and frames
+// drawn while waiting on it just aren't timed.
+pub struct GpuTimer {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
+    period: f32,
+    in_flight: bool,
+    timing_this_frame: bool,
+    ready: Arc<AtomicBool>,
+    // the readback couldn't be mapped, so that measurement is lost
+    failed: Arc<AtomicBool>
+}
+
+impl GpuTimer {
+    // None if the device wasn't created with TIMESTAMP_QUERY (not every backend supports it)
+    pub fn new(device: &Device, queue: &Queue) -> Option<Self> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let size = 2 * wgpu::QUERY_SIZE as u64;
+        Some(Self {
+            query_set: device.create_query_set(&QuerySetDescriptor {
+                label: None,
+                ty: QueryType::Timestamp,
+                count: 2
+            }),
+            resolve_buffer: device.create_buffer(&BufferDescriptor {
+                label: None,
+                size,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false
+            }),
+            readback_buffer: device.create_buffer(&BufferDescriptor {
+                label: None,
+                size,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false
+            }),
+            period: queue.get_timestamp_period(),
+            in_flight: false,
+            timing_this_frame: false,
+            ready: Arc::new(AtomicBool::new(false)),
+            failed: Arc::new(AtomicBool::new(false))
+        })
+    }
+
+    pub fn timestamp_writes(&mut self) -> Option<RenderPassTimestampWrites<'_>> {
+        self.timing_this_frame = !self.in_flight;
+        if !self.timing_this_frame {
+            return None;
+        }
+        Some(RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1)
+        })
+    }
+
+    // Call after the timed pass has ended, before the encoder is submitted.
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if !self.timing_this_frame {
+            return;
+        }
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, self.resolve_buffer.size());
+    }
+
+    // Call after the encoder has been submitted.
+    pub fn submitted(&mut self) {
+        if !self.timing_this_frame {
+            return;
+        }
+        self.timing_this_frame = false;
+        self.in_flight = true;
+        let ready = self.ready.clone();
+        let failed = self.failed.clone();
+        self.readback_buffer.slice(..).map_async(MapMode::Read, move |result| match result {
+            Ok(()) => ready.store(true, Ordering::Release),
+            Err(_) => failed.store(true, Ordering::Release)
+        });
+    }
+
+    // Returns the last measurement if it has finished, optionally blocking until it does.
+    pub fn take(&mut self, device: &Device, wait: bool) -> Option<Duration> {
+        if !self.in_flight {
+            return None;
+        }
+        let poll_type = if wait { PollType::wait_indefinitely() } else { PollType::Poll };
+        if device.poll(poll_type).is_err() {
+            return None;
+        }
+        if self.failed.swap(false, Ordering::Acquire) {
+            // free to time the next frame instead
+            self.in_flight = false;
+            return None;
+        }
+        if !self.ready.swap(false, Ordering::Acquire) {
+            return None;
+        }
+
+        let timestamps: Vec<u64> = {
+            let mapped = self.readback_buffer.slice(..).get_mapped_range();
+            mapped.chunks_exact(8).map(|bytes| u64::from_le_bytes(bytes.try_into().expect("Timestamp is 8 bytes."))).collect()
+        };
+        self.readback_buffer.unmap();
+        self.in_flight = false;
+
+        let ticks = timestamps[1].saturating_sub(timestamps[0]);
+        Some(Duration::from_nanos((ticks as f64 * self.period as f64) as u64))
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+
+use bytemuck::NoUninit;
+use naga::{AddressSpace, Handle, Module, Type, TypeInner};
+
+use crate::preprocessor::ProcessedShader;
+
+// Describes a uniform buffer as both a Rust struct and the WGSL that declares it, so shaders don't
+// have to copy the declaration out of the README and can be checked against the real layout.
+pub struct UniformBlock {
+    pub binding: u32,
+    pub struct_name: &'static str,
+    pub var_name: &'static str,
+    pub fields: &'static [(&'static str, &'static str)],
+    pub size: usize
+}
+
+// Fields starting with an underscore are padding and are left out of the WGSL declaration.
+macro_rules! uniform_block {
+    ($name:ident, $binding:literal, $struct_name:literal, $var_name:literal { $($field:ident: $rust_type:ty => $wgsl_type:literal),* $(,)? }) => {
+        #[repr(C)]
+        #[derive(Copy, Clone, Default, NoUninit)]
+        pub struct $name {
+            $(pub $field: $rust_type),*
+        }
+        impl $name {
+            pub const BLOCK: UniformBlock = UniformBlock {
+                binding: $binding,
+                struct_name: $struct_name,
+                var_name: $var_name,
+                fields: &[$((stringify!($field), $wgsl_type)),*],
+                size: std::mem::size_of::<$name>()
+            };
+        }
+    };
+}
+
+uniform_block!(FragmentInputBuffer, 0, "FragmentInput", "fragment_input" {
+    screen_size: [u32; 2] => "vec2<u32>",
+    frame: u32 => "u32",
+    seed: u32 => "u32",
+    frame_random: u32 => "u32",
+    _padding: u32 => "u32",
+});
+
+// Where the output sits on the canvas it shares with the rest of its span, or just the output
+// itself when it isn't in one.
+uniform_block!(CanvasBuffer, 1, "GpuburdenCanvas", "gpuburden_canvas" {
+    offset: [u32; 2] => "vec2<u32>",
+    size: [u32; 2] => "vec2<u32>",
+    time: f32 => "f32",
+    _padding: u32 => "u32",
+});
+
+// What the compositor says about the output. Rendering to an image has no output, so it's all zero
+// apart from the scale. Newer blocks have gpuburden in their names so they can't clash with the shader's own.
+uniform_block!(OutputBuffer, 2, "GpuburdenOutput", "gpuburden_output" {
+    refresh_rate: f32 => "f32",
+    scale: f32 => "f32",
+    transform: u32 => "u32",
+    index: u32 => "u32",
+    physical_size: [u32; 2] => "vec2<u32>",
+    position: [i32; 2] => "vec2<i32>",
+});
+
+pub const POINTER_CLICKS: usize = 8;
+
+// Where the pointer is over the output, in the output's pixels. Only there for monitors with
+// `pointer = true`.
+uniform_block!(PointerBuffer, 3, "GpuburdenPointer", "gpuburden_pointer" {
+    position: [f32; 2] => "vec2<f32>",
+    buttons: u32 => "u32",
+    inside: u32 => "u32",
+    click_count: u32 => "u32",
+    _padding: [u32; 3] => "array<u32, 3>",
+    // newest first, x, y, canvas time and button
+    clicks: [[f32; 4]; POINTER_CLICKS] => "array<vec4<f32>, 8>",
+});
+
+pub const UNIFORM_BLOCKS: &[UniformBlock] = &[FragmentInputBuffer::BLOCK, CanvasBuffer::BLOCK, OutputBuffer::BLOCK, PointerBuffer::BLOCK];
+
+// The blocks a monitor is given, the pointer is left out unless its config turns it on.
+pub fn blocks(pointer: bool) -> Vec<&'static UniformBlock> {
+    UNIFORM_BLOCKS.iter().filter(|block| pointer || block.binding != PointerBuffer::BLOCK.binding).collect()
+}
+
+impl UniformBlock {
+    fn struct_declaration(&self) -> String {
+        let fields: Vec<String> = self.fields.iter()
+            .filter(|(name, _)| !name.starts_with('_'))
+            .map(|(name, wgsl_type)| format!("    {name}: {wgsl_type}"))
+            .collect();
+        format!("struct {} {{\n{}\n}};\n", self.struct_name, fields.join(",\n"))
+    }
+    fn var_declaration(&self) -> String {
+        format!("@group(0) @binding({}) var<uniform> {}: {};\n", self.binding, self.var_name, self.struct_name)
+    }
+    pub fn declaration(&self) -> String {
+        format!("{}{}", self.struct_declaration(), self.var_declaration())
+    }
+}
+
+// Prepends declarations for the uniform blocks the shader uses without declaring, then compares the
+// ones it does declare against the Rust layout. Blocks the shader never mentions are left out so
+// their names stay free for the shader to use.
+// Declarations that are only missing newer fields still work and just get a warning, anything else is an error.
+pub fn inject_declarations(shader: &mut ProcessedShader, pointer: bool) -> Result<Module, String> {
+    let declared = DeclaredNames::scan(&shader.source);
+    if !pointer && declared.uses(&PointerBuffer::BLOCK) {
+        return Err(format!("the shader uses {}, set `pointer = true` on its monitor in the config to get it", PointerBuffer::BLOCK.var_name));
+    }
+    let mut prelude = String::new();
+    let mut manual = Vec::new();
+    for block in blocks(pointer) {
+        if declared.bindings.contains(&block.binding) {
+            manual.push(block);
+            continue;
+        }
+        if declared.uses(block) {
+            prelude.push_str(&block.declaration());
+        }
+    }
+    if !prelude.is_empty() {
+        shader.prepend(&prelude, "<gpuburden>");
+    }
+
+    let module = shader.validate()?;
+    for block in manual {
+        check_layout(&module, block)?;
+    }
+    Ok(module)
+}
+
+fn check_layout(module: &Module, block: &UniformBlock) -> Result<(), String> {
+    let expected = naga::front::wgsl::parse_str(&block.declaration()).expect("Generated uniform declaration is invalid. Bug report this!");
+    let expected_members = struct_members(&expected, block.binding).expect("Generated uniform declaration has no struct. Bug report this!");
+
+    let Some(members) = struct_members(module, block.binding) else {
+        return Err(format!("binding {} should be `var<uniform> {}: {}`, the expected declaration is:\n{}", block.binding, block.var_name, block.struct_name, block.declaration()));
+    };
+
+    let mut problems = Vec::new();
+    for (index, member) in members.iter().enumerate() {
+        match expected_members.get(index) {
+            Some(expected_member) => {
+                if member.offset != expected_member.offset || describe(module, member.ty) != describe(&expected, expected_member.ty) {
+                    problems.push(format!("field {} (`{}`) doesn't match `{}`", index, member.name.clone().unwrap_or_default(), expected_field(block, index)));
+                }
+            },
+            None => problems.push(format!("field {} (`{}`) doesn't exist in gpuburden's {}", index, member.name.clone().unwrap_or_default(), block.struct_name))
+        }
+    }
+    if !problems.is_empty() {
+        return Err(format!("the shader's declaration of {} is out of date:\n  {}\nremove it to have gpuburden declare it for you, or replace it with:\n{}", block.struct_name, problems.join("\n  "), block.declaration()));
+    }
+
+    if members.len() < expected_members.len() {
+        let missing: Vec<String> = (members.len()..expected_members.len()).map(|index| expected_field(block, index)).collect();
+        println!("the shader's declaration of {} is missing newer fields ({}), remove it to have gpuburden declare it for you.", block.struct_name, missing.join(", "));
+    }
+    Ok(())
+}
+
+fn struct_members(module: &Module, binding: u32) -> Option<Vec<naga::StructMember>> {
+    let (_, variable) = module.global_variables.iter().find(|(_, variable)| {
+        variable.binding.as_ref().is_some_and(|b| b.group == 0 && b.binding == binding)
+    })?;
+    if variable.space != AddressSpace::Uniform {
+        return None;
+    }
+    match &module.types[variable.ty].inner {
+        TypeInner::Struct { members, .. } => Some(members.clone()),
+        _ => None
+    }
+}
+
+fn expected_field(block: &UniformBlock, index: usize) -> String {
+    let (name, wgsl_type) = block.fields.iter().filter(|(name, _)| !name.starts_with('_')).nth(index).expect("Field index out of range.");
+    format!("{name}: {wgsl_type}")
+}
+
+// Type handles from two different modules can't be compared directly
+fn describe(module: &Module, ty: Handle<Type>) -> String {
+    let ty = &module.types[ty];
+    match &ty.inner {
+        TypeInner::Array { base, size, stride } => format!("array<{}, {size:?}, {stride}>", describe(module, *base)),
+        TypeInner::Struct { members, span } => {
+            let members: Vec<String> = members.iter().map(|m| format!("{}@{}", describe(module, m.ty), m.offset)).collect();
+            format!("struct{{{}}}:{span}", members.join(","))
+        },
+        other => format!("{other:?}")
+    }
+}
+
+// Cheap scan for what the shader declares and uses, this has to happen before naga can parse it
+// since the shader may use `fragment_input` without declaring it.
+struct DeclaredNames {
+    identifiers: HashSet<String>,
+    bindings: Vec<u32>
+}
+impl DeclaredNames {
+    fn scan(source: &str) -> Self {
+        let mut identifiers = HashSet::new();
+        let mut bindings = Vec::new();
+        for line in strip_comments(source).lines() {
+            identifiers.extend(line.split(|c: char| !(c.is_alphanumeric() || c == '_')).filter(|word| !word.is_empty()).map(str::to_string));
+
+            let mut rest = line;
+            while let Some(start) = rest.find("@binding(") {
+                rest = &rest[start + "@binding(".len()..];
+                if let Some(end) = rest.find(')') && let Ok(binding) = rest[..end].trim().parse() {
+                    bindings.push(binding);
+                }
+            }
+        }
+        Self { identifiers, bindings }
+    }
+    fn uses(&self, block: &UniformBlock) -> bool {
+        self.bindings.contains(&block.binding) || self.identifiers.contains(block.var_name) || self.identifiers.contains(block.struct_name)
+    }
+}
+
+// The source with `//` and `/* */` comments taken out, so names and bindings that are only
+// mentioned in them don't count. Block comments nest in WGSL.
+fn strip_comments(source: &str) -> String {
+    let mut stripped = String::with_capacity(source.len());
+    let mut depth = 0;
+    let mut line_comment = false;
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\n', _) => {
+                line_comment = false;
+                stripped.push('\n');
+            },
+            _ if line_comment => {},
+            ('/', Some('*')) => {
+                chars.next();
+                depth += 1;
+            },
+            ('*', Some('/')) if depth > 0 => {
+                chars.next();
+                depth -= 1;
+                // keep the words either side apart
+                stripped.push(' ');
+            },
+            _ if depth > 0 => {},
+            ('/', Some('/')) => line_comment = true,
+            _ => stripped.push(c)
+        }
+    }
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use naga::TypeInner;
+
+    use super::UNIFORM_BLOCKS;
+    use crate::preprocessor;
+
+    #[test]
+    fn declarations_match_rust_layout() {
+        for block in UNIFORM_BLOCKS {
+            let module = naga::front::wgsl::parse_str(&block.declaration()).expect("declaration should parse");
+            let (_, ty) = module.types.iter().find(|(_, ty)| ty.name.as_deref() == Some(block.struct_name)).expect("struct should exist");
+            let TypeInner::Struct { span, .. } = ty.inner else {
+                panic!("{} is not a struct", block.struct_name);
+            };
+            assert_eq!(span as usize, block.size, "{} is {span} bytes in WGSL but {} in Rust", block.struct_name, block.size);
+        }
+    }
+
+    #[test]
+    fn injects_missing_declaration() {
+        let source = "@fragment\nfn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(f32(fragment_input.frame)); }\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        super::inject_declarations(&mut shader, false).expect("shader should validate with the injected declaration");
+    }
+
+    #[test]
+    fn leaves_shader_names_alone() {
+        // the usual way of returning several outputs from a fragment shader
+        let source = "struct Output { @location(0) color: vec4<f32> };\n@fragment\nfn fs_main() -> Output {\n    var output: Output;\n    output.color = vec4<f32>(f32(fragment_input.frame));\n    return output;\n}\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        super::inject_declarations(&mut shader, false).expect("shader's own Output should be left alone");
+        assert!(!shader.source.contains("GpuburdenOutput"));
+
+        let source = "@fragment\nfn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(gpuburden_output.scale); }\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        super::inject_declarations(&mut shader, false).expect("shader should validate with the injected declaration");
+    }
+
+    #[test]
+    fn ignores_names_in_comments() {
+        let source = "/* uses gpuburden_output.scale\n   /* and @binding(1), nested */ still a comment gpuburden_canvas */\n@fragment\nfn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(/* gpuburden_pointer */ f32(fragment_input.frame)); } // gpuburden_canvas\n";
+        let declared = super::DeclaredNames::scan(source);
+        assert!(declared.bindings.is_empty());
+        assert!(declared.identifiers.contains("fragment_input"));
+        for name in ["gpuburden_output", "gpuburden_canvas", "gpuburden_pointer", "still"] {
+            assert!(!declared.identifiers.contains(name), "{name} is only in a comment");
+        }
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        super::inject_declarations(&mut shader, false).expect("names in comments shouldn't need the pointer");
+        assert!(!shader.source.contains("struct GpuburdenOutput"));
+    }
+
+    #[test]
+    fn shader_can_declare_its_own_canvas() {
+        let source = "struct Canvas { size: vec2<f32> };\nvar<private> canvas: Canvas;\n@fragment\nfn fs_main() -> @location(0) vec4<f32> {\n    canvas.size = vec2<f32>(gpuburden_canvas.size);\n    return vec4<f32>(canvas.size.x);\n}\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        super::inject_declarations(&mut shader, false).expect("shader's own Canvas should sit alongside gpuburden's");
+    }
+
+    #[test]
+    fn pointer_needs_config() {
+        let source = "@fragment\nfn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(gpuburden_pointer.position, 0.0, 1.0); }\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        assert!(super::inject_declarations(&mut shader, false).is_err());
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        super::inject_declarations(&mut shader, true).expect("shader should validate with the pointer turned on");
+    }
+
+    #[test]
+    fn rejects_outdated_declaration() {
+        let source = "struct FragmentInput { frame: u32, screen_size: vec2<u32> };\n@group(0) @binding(0) var<uniform> fragment_input: FragmentInput;\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        assert!(super::inject_declarations(&mut shader, false).is_err());
+    }
+
+    #[test]
+    fn accepts_older_prefix_declaration() {
+        let source = "struct FragmentInput { screen_size: vec2<u32> };\n@group(0) @binding(0) var<uniform> fragment_input: FragmentInput;\n";
+        let mut shader = preprocessor::preprocess_source("test.wgsl", source, &[]).unwrap();
+        assert!(super::inject_declarations(&mut shader, false).is_ok());
+    }
+}
@@ -0,0 +1,205 @@
+use std::{ops::Range, path::Path};
+
+use toml::de::{DeTable, DeValue};
+
+// Keys gpuburden.toml understands, anything else is most likely a typo.
+const CONFIG_KEYS: &[&str] = &["monitors", "spans", "stats_log_interval", "shader_paths", "show_errors", "strict"];
+const MONITOR_KEYS: &[&str] = &["name", "make", "model", "serial", "description", "physical_size", "shader", "seed", "render_logical_size", "pointer", "layer", "anchor", "size", "margin", "exclusive_zone", "transparent", "show_errors"];
+const SPAN_KEYS: &[&str] = &["outputs", "seed"];
+// the keys that decide which outputs a monitor entry applies to
+const MATCH_KEYS: &[&str] = &["name", "make", "model", "serial", "description", "physical_size"];
+
+pub struct Problem {
+    pub span: Option<Range<usize>>,
+    pub message: String
+}
+
+// The things about a config file that deserializing it doesn't catch.
+pub struct ConfigCheck {
+    pub problems: Vec<Problem>,
+    // where each monitor's shader is, so missing files can be reported once the paths are resolved
+    pub shader_spans: Vec<Option<Range<usize>>>,
+    pub strict: bool
+}
+
+pub fn check(text: &str) -> ConfigCheck {
+    let (table, errors) = DeTable::parse_recoverable(text);
+    let mut problems: Vec<Problem> = errors.iter().map(|e| Problem { span: e.span(), message: e.message().to_string() }).collect();
+    let mut shader_spans = Vec::new();
+    let table = table.get_ref();
+
+    let strict = matches!(table.get("strict").map(|value| value.get_ref()), Some(DeValue::Boolean(true)));
+    unknown_keys(table, CONFIG_KEYS, "", &mut problems);
+
+    if let Some(monitors) = table.get("monitors") && let DeValue::Array(monitors) = monitors.get_ref() {
+        // what each entry matches on, to find ones that can never be used
+        let mut seen: Vec<(String, usize)> = Vec::new();
+        for (index, monitor) in monitors.iter().enumerate() {
+            // the wrong type is reported when deserializing
+            let DeValue::Table(monitor_table) = monitor.get_ref() else {
+                shader_spans.push(None);
+                continue;
+            };
+            unknown_keys(monitor_table, MONITOR_KEYS, &format!("monitors[{index}]"), &mut problems);
+            shader_spans.push(monitor_table.get("shader").map(|shader| shader.span()));
+
+            let enabled = |key: &str| matches!(monitor_table.get(key).map(|value| value.get_ref()), Some(DeValue::Boolean(true)));
+            if enabled("transparent") && enabled("pointer") && let Some(pointer) = monitor_table.get("pointer") {
+                problems.push(Problem {
+                    span: Some(pointer.span()),
+                    message: format!("monitors[{index}] is transparent, so clicks go through it and `pointer` never sees anything")
+                });
+            }
+
+            let matches: Vec<String> = MATCH_KEYS.iter().filter_map(|key| {
+                match monitor_table.get(*key) {
+                    Some(value) => Some(format!("{key}={}", match_value(key, value.get_ref(), text, value.span()))),
+                    None if *key == "name" => Some("name=\"*\"".to_string()),
+                    None => None
+                }
+            }).collect();
+            let matches = matches.join(" ");
+            match seen.iter().find(|(other, _)| *other == matches) {
+                Some((_, line)) => problems.push(Problem {
+                    span: Some(monitor.span()),
+                    message: format!("monitors[{index}] matches the same outputs as the entry on line {line}, so it's never used")
+                }),
+                None => seen.push((matches, line_col(text, monitor.span().start).0))
+            }
+        }
+    }
+
+    if let Some(spans) = table.get("spans") && let DeValue::Array(spans) = spans.get_ref() {
+        for (index, span) in spans.iter().enumerate() {
+            if let DeValue::Table(span_table) = span.get_ref() {
+                unknown_keys(span_table, SPAN_KEYS, &format!("spans[{index}]"), &mut problems);
+            }
+        }
+    }
+
+    ConfigCheck { problems, shader_spans, strict }
+}
+
+// A match key's value written out the same way however it was spelled in the file, so entries
+// that only differ in quoting, number formatting or runs of `*` still count as duplicates.
+fn match_value(key: &str, value: &DeValue, text: &str, span: Range<usize>) -> String {
+    match value {
+        // the serial is looked for as plain text, everything else is a glob
+        DeValue::String(string) if key == "serial" => format!("{string:?}"),
+        DeValue::String(string) => format!("{:?}", normalize_glob(string)),
+        DeValue::Integer(integer) => match i64::from_str_radix(integer.as_str(), integer.radix()) {
+            Ok(r) => r.to_string(),
+            Err(_) => text[span].to_string()
+        },
+        DeValue::Array(values) => {
+            let values: Vec<String> = values.iter().map(|value| match_value(key, value.get_ref(), text, value.span())).collect();
+            format!("[{}]", values.join(","))
+        },
+        // the wrong type is reported when deserializing
+        _ => text[span].to_string()
+    }
+}
+
+// `**` matches the same as `*`, and `*?` the same as `?*`.
+fn normalize_glob(pattern: &str) -> String {
+    let mut normalized = String::new();
+    // wildcards are held back until the next plain character (or the end), then written as the ?s and one *
+    let (mut star, mut questions) = (false, 0);
+    for c in pattern.chars().map(Some).chain([None]) {
+        match c {
+            Some('*') => star = true,
+            Some('?') => questions += 1,
+            _ => {
+                normalized.extend(std::iter::repeat_n('?', questions));
+                if star {
+                    normalized.push('*');
+                }
+                (star, questions) = (false, 0);
+                normalized.extend(c);
+            }
+        }
+    }
+    normalized
+}
+
+fn unknown_keys(table: &DeTable, known: &[&str], context: &str, problems: &mut Vec<Problem>) {
+    for key in table.keys() {
+        if known.contains(&key.get_ref().as_ref()) {
+            continue;
+        }
+        let location = if context.is_empty() { String::new() } else { format!(" in {context}") };
+        problems.push(Problem {
+            span: Some(key.span()),
+            message: format!("unknown key `{}`{location}, expected one of {}", key.get_ref(), known.join(", "))
+        });
+    }
+}
+
+// 1-based, columns are in characters
+fn line_col(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+pub fn report(path: &Path, text: &str, problems: &[Problem]) -> String {
+    let mut report = Vec::new();
+    for problem in problems {
+        let Some(span) = &problem.span else {
+            report.push(format!("error: {}\n  --> {}", problem.message, path.display()));
+            continue;
+        };
+        let (line, column) = line_col(text, span.start);
+        let source = text.lines().nth(line - 1).unwrap_or_default();
+        report.push(format!("error: {}\n  --> {}:{line}:{column}\n   | {source}", problem.message, path.display()));
+    }
+    report.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, line_col};
+
+    #[test]
+    fn reports_unknown_keys_with_location() {
+        let text = "stats_log_intervall = 5\nmonitors = [\n    { name = \"DP-2\", shadr = \"a.wgsl\" },\n]\n";
+        let check = check(text);
+        let locations: Vec<(usize, usize)> = check.problems.iter().map(|p| line_col(text, p.span.clone().unwrap().start)).collect();
+        assert_eq!(locations, vec![(1, 1), (3, 22)]);
+        assert!(check.problems[1].message.contains("`shadr` in monitors[0]"));
+    }
+
+    #[test]
+    fn reports_duplicate_monitors() {
+        let text = "monitors = [\n    { name = \"DP-2\", shader = \"a.wgsl\" },\n    { shader = \"b.wgsl\" },\n    { name = \"DP-2\", shader = \"c.wgsl\" },\n    { name = \"DP-2\", make = \"Dell Inc.\", shader = \"d.wgsl\" },\n]\n";
+        let check = check(text);
+        assert_eq!(check.problems.len(), 1);
+        assert!(check.problems[0].message.contains("line 2"));
+        assert_eq!(check.shader_spans.len(), 4);
+    }
+
+    #[test]
+    fn duplicate_monitors_compare_parsed_values() {
+        let text = "monitors = [\n    { name = \"HDMI-*\", physical_size = [600, 340] },\n    { name = 'HDMI-**', physical_size = [ 0x258,340 ] },\n    { name = \"DP-?*\" },\n    { name = \"DP-*?\" },\n]\n";
+        let check = check(text);
+        let lines: Vec<usize> = check.problems.iter().map(|p| line_col(text, p.span.clone().unwrap().start).0).collect();
+        assert_eq!(lines, vec![3, 5]);
+        assert!(check.problems[1].message.contains("line 4"));
+    }
+
+    #[test]
+    fn reports_pointer_on_transparent_monitors() {
+        let text = "monitors = [\n    { name = \"DP-2\", transparent = true, pointer = true },\n    { name = \"DP-3\", transparent = false, pointer = true },\n]\n";
+        let check = check(text);
+        assert_eq!(check.problems.len(), 1);
+        assert_eq!(line_col(text, check.problems[0].span.clone().unwrap().start), (2, 52));
+    }
+
+    #[test]
+    fn reads_strict_from_broken_files() {
+        let check = check("strict = true\nmonitors = [ { name = \"DP-2\" \n");
+        assert!(check.strict);
+        assert!(!check.problems.is_empty());
+    }
+}
@@ -1,39 +1,102 @@
-use std::{collections::HashMap, ptr::NonNull};
+use std::{collections::HashMap, ptr::NonNull, time::Instant};
 
-use smithay_client_toolkit::{compositor::{CompositorHandler, CompositorState}, delegate_compositor, delegate_layer, delegate_output, delegate_registry, delegate_seat, output::{OutputHandler, OutputState}, registry::{ProvidesRegistryState, RegistryState}, registry_handlers, seat::{SeatHandler, SeatState}, shell::{wlr_layer::{Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface}, WaylandSurface}};
-use wayland_client::{globals::registry_queue_init, protocol::{wl_output::WlOutput, wl_surface::WlSurface}, Connection, Proxy, QueueHandle};
+use smithay_client_toolkit::{compositor::{CompositorHandler, CompositorState, Region}, delegate_compositor, delegate_layer, delegate_output, delegate_pointer, delegate_registry, delegate_seat, delegate_simple, output::{OutputHandler, OutputInfo, OutputState}, globals::ProvidesBoundGlobal, registry::{ProvidesRegistryState, RegistryState, SimpleGlobal}, registry_handlers, seat::{pointer::{PointerEvent, PointerEventKind, PointerHandler}, Capability, SeatHandler, SeatState}, shell::{wlr_layer::{Anchor, KeyboardInteractivity, Layer, LayerShell, LayerShellHandler, LayerSurface}, WaylandSurface}};
+use wayland_client::{globals::registry_queue_init, protocol::{wl_output::{Transform, WlOutput}, wl_pointer::WlPointer, wl_seat::WlSeat, wl_surface::WlSurface}, Connection, Dispatch, Proxy, QueueHandle};
+use wayland_protocols::wp::{fractional_scale::v1::client::{wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, wp_fractional_scale_v1::{self, WpFractionalScaleV1}}, viewporter::client::{wp_viewport::WpViewport, wp_viewporter::WpViewporter}};
 use wgpu::rwh::{RawDisplayHandle, RawWindowHandle, WaylandDisplayHandle, WaylandWindowHandle};
 
-use crate::{configuration::{Configuration, MonitorConfig}, renderer::Renderer};
+use crate::{configuration::{Configuration, Edge, LayerConfig, MonitorConfig, OutputIdentity}, renderer::{Renderer, SpanPlacement}, stats::{OutputStats, StatsRegistry}, uniforms::{OutputBuffer, PointerBuffer, POINTER_CLICKS}};
 
 pub struct WaylandState {
     close: bool,
-    started_drawing: bool,
     targets: HashMap<String, OutputTarget>, // output name -> output target struct
-    config: Option<Configuration>,
+    config: Configuration,
+    stats: StatsRegistry,
+    last_stats_log: Instant,
+    span_starts: HashMap<usize, Instant>, // span index -> shared time base
+    pointer: Option<WlPointer>, // only taken when a monitor wants it
 
     conn: Connection,
     compositor: CompositorState,
     layer_shell: LayerShell,
+    // both optional, without them only integer scales are possible
+    viewporter: Option<SimpleGlobal<WpViewporter, 1>>,
+    fractional_scale_manager: Option<SimpleGlobal<WpFractionalScaleManagerV1, 1>>,
 
     registry_state: RegistryState,
     seat_state: SeatState,
     output_state: OutputState,
 }
 struct OutputTarget {
+    name: String,
     output: WlOutput,
     layer: LayerSurface,
     surface: WlSurface,
     renderer: Option<Renderer>,
-    configured: bool
+    stats: OutputStats,
+    configured: bool,
+
+    logical_size: (u32, u32),
+    requested_size: (u32, u32), // zero where the compositor decides
+    span: Option<usize>,
+    transform: Transform,
+    buffer_scale: i32,
+    fractional_scale: Option<f64>, // preferred over buffer_scale when there's a viewport
+    viewport: Option<WpViewport>,
+    _fractional_scale: Option<WpFractionalScaleV1>,
+    render_logical_size: bool,
+    track_pointer: bool
+}
+
+impl OutputTarget {
+    // Fractional scales need a viewport to tell the compositor how big the buffer is meant to be.
+    fn scale(&self) -> f64 {
+        if self.render_logical_size {
+            return 1.0;
+        }
+        match (self.fractional_scale, &self.viewport) {
+            (Some(scale), Some(_)) => scale,
+            _ => self.buffer_scale as f64
+        }
+    }
+
+    // Tells the compositor how the buffer maps onto the layer's logical size, and resizes the
+    // renderer to the physical size if it has changed.
+    fn apply_scale(&mut self) {
+        let (width, height) = self.logical_size;
+        if width == 0 || height == 0 {
+            return;
+        }
+        match &self.viewport {
+            Some(viewport) => {
+                self.surface.set_buffer_scale(1);
+                viewport.set_destination(width as i32, height as i32);
+            },
+            None => self.surface.set_buffer_scale(self.scale() as i32)
+        }
+
+        let scale = self.scale();
+        let physical = ((width as f64 * scale).round() as u32, (height as f64 * scale).round() as u32);
+        if let Some(renderer) = &mut self.renderer && (renderer.width, renderer.height) != physical {
+            renderer.configure_surface(physical.0, physical.1);
+            println!("{} rendering at {}x{} (scale {scale})", self.name, physical.0, physical.1);
+        }
+    }
 }
 
 impl CompositorHandler for WaylandState {
-    fn frame(&mut self, _conn: &wayland_client::Connection, qh: &wayland_client::QueueHandle<Self>, _surface: &wayland_client::protocol::wl_surface::WlSurface, _time: u32) {
-        self.draw(qh);
+    fn frame(&mut self, _conn: &wayland_client::Connection, qh: &wayland_client::QueueHandle<Self>, surface: &wayland_client::protocol::wl_surface::WlSurface, _time: u32) {
+        self.draw(qh, surface, true);
     }
 
-    fn scale_factor_changed(&mut self, _conn: &wayland_client::Connection, _qh: &wayland_client::QueueHandle<Self>, _surface: &wayland_client::protocol::wl_surface::WlSurface, _new_factor: i32) {}
+    fn scale_factor_changed(&mut self, _conn: &wayland_client::Connection, _qh: &wayland_client::QueueHandle<Self>, surface: &wayland_client::protocol::wl_surface::WlSurface, new_factor: i32) {
+        for target in self.targets.values_mut().filter(|target| target.surface == *surface) {
+            target.buffer_scale = new_factor;
+            target.apply_scale();
+        }
+        self.update_layout();
+    }
+    // buffers are drawn upright and the compositor rotates them, output transforms are handled in update_output
     fn transform_changed(&mut self, _conn: &wayland_client::Connection, _qh: &wayland_client::QueueHandle<Self>, _surface: &wayland_client::protocol::wl_surface::WlSurface, _new_transform: wayland_client::protocol::wl_output::Transform) {}
     fn surface_enter(&mut self, _conn: &wayland_client::Connection, _qh: &wayland_client::QueueHandle<Self>, _surface: &wayland_client::protocol::wl_surface::WlSurface, _output: &wayland_client::protocol::wl_output::WlOutput) {}
     fn surface_leave(&mut self, _conn: &wayland_client::Connection, _qh: &wayland_client::QueueHandle<Self>, _surface: &wayland_client::protocol::wl_surface::WlSurface, _output: &wayland_client::protocol::wl_output::WlOutput) {}
@@ -50,43 +113,111 @@ impl OutputHandler for WaylandState {
             None => return, // don't bother with it
         };
 
-        let name = match output_info.name {
+        let identity = match output_identity(&output_info) {
             Some(r) => r,
             None => return,
         };
+        let name = identity.name.clone();
+        verbose!("{name} is {} {} ({}), {}x{}mm", identity.make, identity.model, identity.description, identity.physical_size.0, identity.physical_size.1);
+
+        let Some(monitor_config) = self.config.monitor_config(&identity) else {
+            println!("output {name} skipped as it's not defined in the config or filtered out by --output.");
+            return;
+        };
+
+        let refresh_rate = output_info.modes.iter().find(|mode| mode.current).map(|mode| mode.refresh_rate);
 
-        if let Some(config) = &self.config {
-            let monitor_config = config.monitor_config(&name);
-            if monitor_config.is_none() {
-                println!("output {name} skipped as it's not defined in the config.");
-                return;
+        let (width, height) = output_logical_size(&output_info);
+        let requested_size = monitor_config.layer_size((width, height));
+        let layer_kind = match monitor_config.layer {
+            LayerConfig::Background => Layer::Background,
+            LayerConfig::Bottom => Layer::Bottom,
+            LayerConfig::Top => Layer::Top,
+            LayerConfig::Overlay => Layer::Overlay
+        };
+        let mut anchor = Anchor::empty();
+        for (edge, bit) in [(Edge::Top, Anchor::TOP), (Edge::Bottom, Anchor::BOTTOM), (Edge::Left, Anchor::LEFT), (Edge::Right, Anchor::RIGHT)] {
+            if monitor_config.anchored(edge) {
+                anchor |= bit;
             }
         }
+        let [top, right, bottom, left] = monitor_config.margin;
 
-        let mut width: u32 = output_info.modes[0].dimensions.0 as u32;
-        let mut height: u32 = output_info.modes[0].dimensions.1 as u32;
-        if let Some(size) = output_info.logical_size {
-            width = size.0 as u32;
-            height = size.1 as u32;
-        }
         let surface = self.compositor.create_surface(qh);
-        let layer = self.layer_shell.create_layer_surface(qh, surface.clone(), Layer::Background, Some(format!("gpuburden-{name}")), Some(&output));
+        let layer = self.layer_shell.create_layer_surface(qh, surface.clone(), layer_kind, Some(format!("gpuburden-{name}")), Some(&output));
         layer.set_keyboard_interactivity(KeyboardInteractivity::None);
-        layer.set_size(width, height);
-        layer.set_anchor(Anchor::LEFT | Anchor::TOP);
+        layer.set_size(requested_size.0, requested_size.1);
+        layer.set_anchor(anchor);
+        layer.set_margin(top, right, bottom, left);
+        layer.set_exclusive_zone(monitor_config.exclusive_zone.unwrap_or(-1));
+        if monitor_config.transparent {
+            // an empty input region lets clicks through to whatever is below
+            match Region::new(&self.compositor) {
+                Ok(region) => surface.set_input_region(Some(region.wl_region())),
+                Err(e) => println!("failed to make {name} click-through: {e}")
+            }
+        }
+        let viewport = self.viewporter.as_ref().and_then(|viewporter| viewporter.bound_global().ok()).map(|viewporter| viewporter.get_viewport(&surface, qh, ()));
+        let fractional_scale = self.fractional_scale_manager.as_ref().and_then(|manager| manager.bound_global().ok()).map(|manager| manager.get_fractional_scale(&surface, qh, surface.clone()));
         layer.commit();
 
         let target = OutputTarget {
+            name: name.clone(),
             output,
             layer,
             surface,
             renderer: None,
-            configured: false
+            stats: OutputStats::new(&name, &monitor_config.shader, refresh_rate),
+            configured: false,
+
+            logical_size: (0, 0),
+            requested_size,
+            span: self.config.span(&name),
+            transform: output_info.transform,
+            buffer_scale: output_info.scale_factor,
+            fractional_scale: None,
+            viewport,
+            _fractional_scale: fractional_scale,
+            render_logical_size: monitor_config.render_logical_size,
+            // the uniform is still there for the shader, it just stays at 0
+            track_pointer: monitor_config.pointer && !monitor_config.transparent
         };
+        if let Some(span) = target.span {
+            self.span_starts.entry(span).or_insert_with(Instant::now);
+            println!("{name} is in span {span}");
+        }
         println!("new output {name} ({width}x{height})");
         self.targets.insert(name, target);
     }
-    fn update_output(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _output: wayland_client::protocol::wl_output::WlOutput) {}
+    // Outputs changing mode or getting rotated while running.
+    fn update_output(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, output: wayland_client::protocol::wl_output::WlOutput) {
+        let Some(info) = self.output_state.info(&output) else {
+            return;
+        };
+        for target in self.targets.values_mut().filter(|target| target.output == output) {
+            if info.transform != target.transform {
+                println!("{} transform changed to {:?}", target.name, info.transform);
+                target.transform = info.transform;
+                if let Some(renderer) = &mut target.renderer {
+                    renderer.transform = u32::from(info.transform);
+                }
+            }
+
+            // surfaces stretched between edges are resized by the compositor, the rest follow the output
+            // and get the renderer resized when the compositor configures the layer with the new size
+            let requested_size = match output_identity(&info).and_then(|identity| self.config.monitor_config(&identity)) {
+                Some(r) => r.layer_size(output_logical_size(&info)),
+                None => continue
+            };
+            if target.configured && requested_size != target.requested_size {
+                target.requested_size = requested_size;
+                target.layer.set_size(requested_size.0, requested_size.1);
+                target.layer.commit();
+            }
+        }
+        // the output may also have been moved
+        self.update_layout();
+    }
     fn output_destroyed(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _output: wayland_client::protocol::wl_output::WlOutput) {}
 }
 
@@ -96,6 +227,7 @@ impl LayerShellHandler for WaylandState {
     }
 
     fn configure(&mut self, _conn: &wayland_client::Connection, qh: &QueueHandle<Self>, layer: &LayerSurface, configure: smithay_client_toolkit::shell::wlr_layer::LayerSurfaceConfigure, _serial: u32) {
+        let mut first_configured = Vec::new();
         for target in &mut self.targets {
             if target.1.layer != *layer {
                 continue
@@ -103,53 +235,51 @@ impl LayerShellHandler for WaylandState {
 
             let target = target.1;
             let info = self.output_state.info(&target.output).expect("Failed to get info for display.");
-            let name = match info.name {
+            let identity = match output_identity(&info) {
                 Some(r) => r,
                 None => return,
             };
+            let name = identity.name.clone();
 
-            let mut width = configure.new_size.0;
-            let mut height = configure.new_size.1;
-            if let Some(size) = info.logical_size {
-                width = size.0 as u32;
-                height = size.1 as u32;
-            } else {
-                match info.transform {
-                    wayland_client::protocol::wl_output::Transform::_90 | wayland_client::protocol::wl_output::Transform::_270 |
-                    wayland_client::protocol::wl_output::Transform::Flipped90 | wayland_client::protocol::wl_output::Transform::Flipped270 => {
-                        width = configure.new_size.1;
-                        height = configure.new_size.0;
-                    },
-                    _ => {}
-                }
-            }
+            // the configure is already in surface coordinates, so it has the transform applied. A zero
+            // means the compositor left it to us.
+            let output_size = output_logical_size(&info);
+            let pick = |configured: u32, requested: u32, output: u32| match (configured, requested) {
+                (0, 0) => output,
+                (0, requested) => requested,
+                (configured, _) => configured
+            };
+            let width = pick(configure.new_size.0, target.requested_size.0, output_size.0);
+            let height = pick(configure.new_size.1, target.requested_size.1, output_size.1);
 
             if target.configured {
-                println!("configure called on already-configed monitor {name}");
-                return;
+                target.logical_size = (width, height);
+                target.apply_scale();
+                println!("{name} reconfigured for {width}x{height}");
+                continue;
             }
 
             // setup renderer
-            let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
-                    NonNull::new(self.conn.backend().display_ptr() as *mut _).expect("Failed to create display handle for wgpu.")
-            ));
-            let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(
-                    NonNull::new(target.surface.id().as_ptr() as *mut _).expect("Failed to create window handle for wgpu.")
-            ));
-            let config: Option<MonitorConfig> = if let Some(config) = &self.config {
-                config.monitor_config(&name)
-            } else {
-                None
-            };
+            let (raw_display_handle, raw_window_handle) = raw_handles(&self.conn, &target.surface);
+            let config: Option<MonitorConfig> = self.config.monitor_config(&identity);
             let mut renderer = Renderer::for_layer(raw_display_handle, raw_window_handle, &config);
-            renderer.configure_surface(width, height);
+            renderer.transform = u32::from(info.transform);
+            let adapter_info = renderer.adapter_info();
+            verbose!("{name} is rendering with {} ({:?})", adapter_info.name, adapter_info.backend);
+            if !renderer.enable_gpu_timing() {
+                verbose!("{name} can't use timestamp queries, gpu time won't be in the stats.");
+            }
             target.renderer = Some(renderer);
+            target.logical_size = (width, height);
+            target.apply_scale();
             target.configured = true;
+            first_configured.push(target.surface.clone());
             println!("{name} configured for {width}x{height}");
         }
-        if !self.started_drawing {
-            self.draw(qh);
-            self.started_drawing = true;
+        self.update_layout();
+        // after the first frame each surface keeps itself going with frame callbacks
+        for surface in first_configured {
+            self.draw(qh, &surface, false);
         }
     }
 }
@@ -159,12 +289,81 @@ impl SeatHandler for WaylandState {
         &mut self.seat_state
     }
 
-    fn new_capability(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _seat: wayland_client::protocol::wl_seat::WlSeat, _capability: smithay_client_toolkit::seat::Capability) {}
-    fn remove_capability(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _seat: wayland_client::protocol::wl_seat::WlSeat, _capability: smithay_client_toolkit::seat::Capability) {}
+    fn new_capability(&mut self, _conn: &wayland_client::Connection, qh: &QueueHandle<Self>, seat: WlSeat, capability: Capability) {
+        if capability != Capability::Pointer || self.pointer.is_some() || !self.config.wants_pointer() {
+            return;
+        }
+        match self.seat_state.get_pointer(qh, &seat) {
+            Ok(r) => self.pointer = Some(r),
+            Err(e) => println!("failed to get the pointer, shaders won't see it: {e}")
+        }
+    }
+    fn remove_capability(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _seat: WlSeat, capability: Capability) {
+        if capability == Capability::Pointer && let Some(pointer) = self.pointer.take() {
+            pointer.release();
+        }
+    }
     fn new_seat(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _seat: wayland_client::protocol::wl_seat::WlSeat) {}
     fn remove_seat(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _seat: wayland_client::protocol::wl_seat::WlSeat) {}
 }
 
+// Only sees the pointer while it's over the desktop, windows on top of the background get it instead.
+impl PointerHandler for WaylandState {
+    fn pointer_frame(&mut self, _conn: &wayland_client::Connection, _qh: &QueueHandle<Self>, _pointer: &WlPointer, events: &[PointerEvent]) {
+        for event in events {
+            let Some(target) = self.targets.values_mut().find(|target| target.surface == event.surface) else {
+                continue;
+            };
+            if !target.track_pointer {
+                continue;
+            }
+            let scale = target.scale();
+            let Some(renderer) = &mut target.renderer else {
+                continue;
+            };
+            let position = [(event.position.0 * scale) as f32, (event.position.1 * scale) as f32];
+            let time = renderer.time();
+            update_pointer(&mut renderer.pointer, &event.kind, position, time);
+        }
+    }
+}
+
+// What the shader sees after one pointer event, position is in the output's pixels and time is
+// the canvas time the event happened at.
+fn update_pointer(pointer: &mut PointerBuffer, kind: &PointerEventKind, position: [f32; 2], time: f32) {
+    match *kind {
+        PointerEventKind::Enter { .. } => {
+            pointer.inside = 1;
+            pointer.position = position;
+        },
+        PointerEventKind::Leave { .. } => {
+            pointer.inside = 0;
+            pointer.buttons = 0;
+        },
+        PointerEventKind::Motion { .. } => pointer.position = position,
+        PointerEventKind::Press { button, .. } => {
+            let bit = pointer_button(button);
+            pointer.buttons |= bit;
+            pointer.position = position;
+            pointer.clicks.copy_within(..POINTER_CLICKS - 1, 1);
+            pointer.clicks[0] = [position[0], position[1], time, bit as f32];
+            pointer.click_count += 1;
+        },
+        PointerEventKind::Release { button, .. } => pointer.buttons &= !pointer_button(button),
+        PointerEventKind::Axis { .. } => {}
+    }
+}
+
+// Linux input codes to the bits in `pointer.buttons`
+fn pointer_button(button: u32) -> u32 {
+    match button {
+        0x110 => 1, // BTN_LEFT
+        0x111 => 2, // BTN_RIGHT
+        0x112 => 4, // BTN_MIDDLE
+        _ => 0
+    }
+}
+
 impl ProvidesRegistryState for WaylandState {
     fn registry(&mut self) -> &mut RegistryState {
         &mut self.registry_state
@@ -173,26 +372,175 @@ impl ProvidesRegistryState for WaylandState {
 }
 
 impl WaylandState {
-    pub fn draw(&mut self, qh: &QueueHandle<Self>) {
-        for render_target in &mut self.targets {
-            let target = render_target.1;
+    // Everything the shaders are told about where the outputs are and what they're like, after any
+    // of them are configured, moved or rescaled.
+    fn update_layout(&mut self) {
+        self.update_spans();
+        self.update_output_info();
+    }
+
+    fn update_output_info(&mut self) {
+        // outputs are numbered left to right, then top to bottom
+        let mut positions: Vec<((i32, i32), String)> = self.targets.values()
+            .filter_map(|target| self.output_state.info(&target.output).map(|info| (info.logical_position.unwrap_or(info.location), target.name.clone())))
+            .collect();
+        positions.sort();
+
+        for (index, (position, name)) in positions.iter().enumerate() {
+            let Some(target) = self.targets.get_mut(name) else {
+                continue;
+            };
+            let Some(info) = self.output_state.info(&target.output) else {
+                continue;
+            };
+            let scale = target.scale();
+            let Some(renderer) = &mut target.renderer else {
+                continue;
+            };
+            renderer.output = OutputBuffer {
+                refresh_rate: info.modes.iter().find(|mode| mode.current).map(|mode| mode.refresh_rate as f32 / 1000.0).unwrap_or_default(),
+                scale: scale as f32,
+                index: index as u32,
+                physical_size: [info.physical_size.0.max(0) as u32, info.physical_size.1.max(0) as u32],
+                position: [position.0, position.1],
+                ..Default::default()
+            };
+        }
+    }
+
+    // Works out where every spanned output sits in the bounding box of its span, from where the
+    // compositor has placed them.
+    fn update_spans(&mut self) {
+        let mut rects: HashMap<usize, Vec<(i32, i32, i32, i32)>> = HashMap::new();
+        let mut positions: HashMap<String, (i32, i32)> = HashMap::new();
+        for target in self.targets.values() {
+            let (Some(span), Some(info)) = (target.span, self.output_state.info(&target.output)) else {
+                continue;
+            };
+            let position = info.logical_position.unwrap_or(info.location);
+            // the output's size rather than the surface's, which is smaller for panels
+            let (width, height) = output_logical_size(&info);
+            rects.entry(span).or_default().push((position.0, position.1, width as i32, height as i32));
+            positions.insert(target.name.clone(), position);
+        }
+
+        for target in self.targets.values_mut() {
+            let (Some(span), Some(position)) = (target.span, positions.get(&target.name)) else {
+                continue;
+            };
+            let span_rects = &rects[&span];
+            let left = span_rects.iter().map(|r| r.0).min().unwrap_or_default();
+            let top = span_rects.iter().map(|r| r.1).min().unwrap_or_default();
+            let right = span_rects.iter().map(|r| r.0 + r.2).max().unwrap_or_default();
+            let bottom = span_rects.iter().map(|r| r.1 + r.3).max().unwrap_or_default();
+
+            // in this output's pixels, so it lines up with the fragment coordinates
+            let scale = target.scale();
+            let scaled = |value: i32| (value as f64 * scale).round() as u32;
+            let placement = SpanPlacement {
+                offset: [scaled(position.0 - left), scaled(position.1 - top)],
+                size: [scaled(right - left), scaled(bottom - top)]
+            };
             if let Some(renderer) = &mut target.renderer {
+                if renderer.span != Some(placement) {
+                    verbose!("{} is at {:?} of a {:?} canvas", target.name, placement.offset, placement.size);
+                }
+                renderer.span = Some(placement);
+                renderer.time_base = self.span_starts.get(&span).copied();
+            }
+        }
+    }
+
+    // Draws the next frame on one surface, after_callback is whether the compositor asked for it
+    // with the frame callback the last one requested.
+    pub fn draw(&mut self, qh: &QueueHandle<Self>, surface: &WlSurface, after_callback: bool) {
+        for target in self.targets.values_mut().filter(|target| target.surface == *surface) {
+            if let Some(renderer) = &mut target.renderer {
+                let start = Instant::now();
                 renderer.draw();
+                let cpu_time = start.elapsed();
+                if let Some(snapshot) = target.stats.record_frame(Instant::now(), cpu_time, renderer.take_gpu_time(false), after_callback) {
+                    self.stats.publish(snapshot);
+                }
                 // target.layer.wl_surface().damage_buffer(0, 0, renderer.width as i32, renderer.height as i32);
             }
             target.layer.wl_surface().frame(qh, target.layer.wl_surface().clone());
             target.layer.commit();
         }
+
+        if let Some(interval) = self.config.stats_log_interval() && self.last_stats_log.elapsed() >= interval {
+            print!("{}", self.stats.report());
+            self.last_stats_log = Instant::now();
+        }
+    }
+}
+
+// What wgpu needs to draw to a surface.
+pub fn raw_handles(conn: &Connection, surface: &WlSurface) -> (RawDisplayHandle, RawWindowHandle) {
+    let raw_display_handle = RawDisplayHandle::Wayland(WaylandDisplayHandle::new(
+            NonNull::new(conn.backend().display_ptr() as *mut _).expect("Failed to create display handle for wgpu.")
+    ));
+    let raw_window_handle = RawWindowHandle::Wayland(WaylandWindowHandle::new(
+            NonNull::new(surface.id().as_ptr() as *mut _).expect("Failed to create window handle for wgpu.")
+    ));
+    (raw_display_handle, raw_window_handle)
+}
+
+// The output's size in surface coordinates, which is what layers are sized in. xdg-output's logical
+// size already has the transform and scale applied, the mode doesn't.
+pub fn output_logical_size(info: &OutputInfo) -> (u32, u32) {
+    if let Some((width, height)) = info.logical_size {
+        return (width as u32, height as u32);
+    }
+    let mode = info.modes.iter().find(|mode| mode.current).or(info.modes.first());
+    let (width, height) = mode.map(|mode| mode.dimensions).unwrap_or_default();
+    let (width, height) = match info.transform {
+        Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270 => (height, width),
+        _ => (width, height)
+    };
+    let scale = info.scale_factor.max(1);
+    ((width / scale) as u32, (height / scale) as u32)
+}
+
+// None for outputs without a name, since they can't be told apart
+pub fn output_identity(info: &OutputInfo) -> Option<OutputIdentity> {
+    Some(OutputIdentity {
+        name: info.name.clone()?,
+        make: info.make.clone(),
+        model: info.model.clone(),
+        description: info.description.clone().unwrap_or_default(),
+        physical_size: info.physical_size
+    })
+}
+
+impl Dispatch<WpViewport, ()> for WaylandState {
+    fn event(_state: &mut Self, _proxy: &WpViewport, _event: <WpViewport as Proxy>::Event, _data: &(), _conn: &Connection, _qh: &QueueHandle<Self>) {}
+}
+
+impl Dispatch<WpFractionalScaleV1, WlSurface> for WaylandState {
+    fn event(state: &mut Self, _proxy: &WpFractionalScaleV1, event: wp_fractional_scale_v1::Event, surface: &WlSurface, _conn: &Connection, _qh: &QueueHandle<Self>) {
+        let wp_fractional_scale_v1::Event::PreferredScale { scale } = event else {
+            return;
+        };
+        for target in state.targets.values_mut().filter(|target| target.surface == *surface) {
+            // sent in 120ths
+            target.fractional_scale = Some(scale as f64 / 120.0);
+            target.apply_scale();
+        }
+        state.update_layout();
     }
 }
 
 delegate_compositor!(WaylandState);
 delegate_output!(WaylandState);
 delegate_seat!(WaylandState);
+delegate_pointer!(WaylandState);
 delegate_layer!(WaylandState);
 delegate_registry!(WaylandState);
+delegate_simple!(WaylandState, WpViewporter, 1);
+delegate_simple!(WaylandState, WpFractionalScaleManagerV1, 1);
 
-pub fn start(config: Option<Configuration>) {
+pub fn start(config: Configuration, stats: StatsRegistry) {
     let conn = Connection::connect_to_env().expect("Unable to connect to a compositor.");
     let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
     let qh = event_queue.handle();
@@ -202,13 +550,18 @@ pub fn start(config: Option<Configuration>) {
 
     let mut state = WaylandState {
         close: false,
-        started_drawing: false,
         targets: HashMap::new(),
         config,
+        stats,
+        last_stats_log: Instant::now(),
+        span_starts: HashMap::new(),
+        pointer: None,
 
         conn,
         compositor,
         layer_shell,
+        viewporter: SimpleGlobal::bind(&globals, &qh).ok(),
+        fractional_scale_manager: SimpleGlobal::bind(&globals, &qh).ok(),
 
         registry_state: RegistryState::new(&globals),
         seat_state: SeatState::new(&globals, &qh),
@@ -224,8 +577,50 @@ pub fn start(config: Option<Configuration>) {
 
     for render_target in &mut state.targets {
         let target = render_target.1;
+        state.stats.remove(render_target.0);
         if let Some(renderer) = target.renderer.take() {
             renderer.free_surface();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smithay_client_toolkit::seat::pointer::PointerEventKind;
+
+    use super::update_pointer;
+    use crate::uniforms::{PointerBuffer, POINTER_CLICKS};
+
+    #[test]
+    fn pointer_tracks_buttons_and_clicks() {
+        let mut pointer = PointerBuffer::default();
+        update_pointer(&mut pointer, &PointerEventKind::Enter { serial: 0 }, [10.0, 20.0], 0.0);
+        assert_eq!((pointer.inside, pointer.position), (1, [10.0, 20.0]));
+
+        update_pointer(&mut pointer, &PointerEventKind::Press { time: 0, button: 0x110, serial: 0 }, [30.0, 40.0], 1.5);
+        update_pointer(&mut pointer, &PointerEventKind::Press { time: 0, button: 0x111, serial: 0 }, [50.0, 60.0], 2.0);
+        assert_eq!(pointer.buttons, 3);
+        assert_eq!(pointer.click_count, 2);
+        assert_eq!(pointer.clicks[0], [50.0, 60.0, 2.0, 2.0]);
+        assert_eq!(pointer.clicks[1], [30.0, 40.0, 1.5, 1.0]);
+
+        update_pointer(&mut pointer, &PointerEventKind::Release { time: 0, button: 0x110, serial: 0 }, [50.0, 60.0], 2.5);
+        assert_eq!(pointer.buttons, 2);
+        update_pointer(&mut pointer, &PointerEventKind::Motion { time: 0 }, [70.0, 80.0], 3.0);
+        assert_eq!(pointer.position, [70.0, 80.0]);
+        update_pointer(&mut pointer, &PointerEventKind::Leave { serial: 0 }, [70.0, 80.0], 3.0);
+        assert_eq!((pointer.inside, pointer.buttons), (0, 0));
+    }
+
+    #[test]
+    fn pointer_keeps_newest_clicks() {
+        let mut pointer = PointerBuffer::default();
+        for index in 0..POINTER_CLICKS + 2 {
+            update_pointer(&mut pointer, &PointerEventKind::Press { time: 0, button: 0x112, serial: 0 }, [index as f32, 0.0], index as f32);
+        }
+        assert_eq!(pointer.click_count as usize, POINTER_CLICKS + 2);
+        assert_eq!(pointer.clicks[0][0], (POINTER_CLICKS + 1) as f32);
+        assert_eq!(pointer.clicks[POINTER_CLICKS - 1][0], 2.0);
+        assert_eq!(pointer.clicks[0][3], 4.0);
+    }
+}
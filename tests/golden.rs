@@ -0,0 +1,136 @@
+// Renders the bundled example shaders offscreen and compares them against the reference images in
+// tests/golden. Run with GPUBURDEN_BLESS=1 to (re)generate the references after an intended change.
+// Differing pixels get written to golden-diffs in cargo's test temp directory (target/tmp by
+// default) so failures can actually be looked at.
+// They need a wgpu adapter (a software one is fine) and fail without one, set GPUBURDEN_SKIP_GOLDEN=1
+// to skip them on machines that can't have one.
+use std::{env, fs::{self, File}, io::BufWriter, path::{Path, PathBuf}, process::Command};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 144;
+const FRAME: &str = "600";
+const SEED: &str = "1234";
+
+// Per-pixel threshold on the YIQ colour difference (0 to 1), same idea as pixelmatch.
+const PIXEL_THRESHOLD: f64 = 0.02;
+// How much of the image is allowed to be over that threshold, GPUs don't all agree on the last few bits.
+const MAX_DIFFERENT_FRACTION: f64 = 0.005;
+
+struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>
+}
+
+#[test]
+fn default_shader() {
+    check_shader("default-shader");
+}
+
+#[test]
+fn distorted_noise() {
+    check_shader("distorted-noise");
+}
+
+fn check_shader(name: &str) {
+    if env::var_os("GPUBURDEN_SKIP_GOLDEN").is_some() {
+        println!("skipping {name}, GPUBURDEN_SKIP_GOLDEN is set");
+        return;
+    }
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden-diffs");
+    fs::create_dir_all(&out_dir).unwrap_or_else(|e| panic!("failed to create {}: {e}", out_dir.display()));
+
+    let actual_path = out_dir.join(format!("{name}-actual.png"));
+    let output = Command::new(env!("CARGO_BIN_EXE_gpuburden"))
+        .args(["render", "--size", &format!("{WIDTH}x{HEIGHT}"), "--frame", FRAME, "--seed", SEED, "--shader"])
+        .arg(root.join(format!("examples/{name}.wgsl")))
+        .arg("-o")
+        .arg(&actual_path)
+        .output()
+        .expect("failed to run gpuburden");
+    if !output.status.success() {
+        panic!("gpuburden render failed for {name}:\n{}", String::from_utf8_lossy(&output.stderr));
+    }
+    let actual = read_png(&actual_path);
+
+    let reference_path = root.join(format!("tests/golden/{name}.png"));
+    if env::var_os("GPUBURDEN_BLESS").is_some() {
+        fs::copy(&actual_path, &reference_path).expect("failed to update the reference image");
+        return;
+    }
+    let reference = read_png(&reference_path);
+    assert_eq!((actual.width, actual.height), (reference.width, reference.height), "{name} rendered at the wrong size");
+
+    let (different, diff) = compare(&reference, &actual);
+    let fraction = different as f64 / (WIDTH * HEIGHT) as f64;
+    if fraction > MAX_DIFFERENT_FRACTION {
+        let diff_path = out_dir.join(format!("{name}-diff.png"));
+        write_png(&diff_path, &diff);
+        panic!(
+            "{name} differs from its reference in {different} pixels ({:.2}%), see {} and {}",
+            fraction * 100.,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// Returns how many pixels are over the threshold, and an image with those pixels in red over a
+// faded copy of the reference.
+fn compare(reference: &Image, actual: &Image) -> (usize, Image) {
+    let mut different = 0;
+    let mut diff = Vec::with_capacity(reference.pixels.len());
+    for (expected, got) in reference.pixels.chunks(4).zip(actual.pixels.chunks(4)) {
+        if yiq_delta(expected, got) > PIXEL_THRESHOLD {
+            different += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let luma = (luma(expected) * 0.1 * 255. + 255. * 0.9) as u8;
+            diff.extend_from_slice(&[luma, luma, luma, 255]);
+        }
+    }
+    (different, Image { width: reference.width, height: reference.height, pixels: diff })
+}
+
+fn luma(pixel: &[u8]) -> f64 {
+    (pixel[0] as f64 * 0.29889531 + pixel[1] as f64 * 0.58662247 + pixel[2] as f64 * 0.11448223) / 255.
+}
+
+// Colour difference in YIQ space, which weights brightness changes more than hue changes the way eyes do.
+// Normalized so that 1 is black vs white.
+fn yiq_delta(a: &[u8], b: &[u8]) -> f64 {
+    let yiq = |p: &[u8]| {
+        let (r, g, b) = (p[0] as f64 / 255., p[1] as f64 / 255., p[2] as f64 / 255.);
+        (
+            r * 0.29889531 + g * 0.58662247 + b * 0.11448223,
+            r * 0.59597799 - g * 0.27417610 - b * 0.32180189,
+            r * 0.21147017 - g * 0.52261711 + b * 0.31114694
+        )
+    };
+    let (y1, i1, q1) = yiq(a);
+    let (y2, i2, q2) = yiq(b);
+    let (dy, di, dq) = (y1 - y2, i1 - i2, q1 - q2);
+    ((0.5053 * dy * dy + 0.299 * di * di + 0.1957 * dq * dq) / 0.5053).sqrt()
+}
+
+fn read_png(path: &Path) -> Image {
+    let file = File::open(path).unwrap_or_else(|e| panic!("failed to open {}: {e}", path.display()));
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::ALPHA);
+    let mut reader = decoder.read_info().unwrap_or_else(|e| panic!("failed to decode {}: {e}", path.display()));
+    let mut pixels = vec![0; reader.output_buffer_size().expect("image too large")];
+    let info = reader.next_frame(&mut pixels).unwrap_or_else(|e| panic!("failed to decode {}: {e}", path.display()));
+    pixels.truncate(info.buffer_size());
+    Image { width: info.width, height: info.height, pixels }
+}
+
+fn write_png(path: &Path, image: &Image) {
+    let file = File::create(path).unwrap_or_else(|e| panic!("failed to create {}: {e}", path.display()));
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(&image.pixels))
+        .unwrap_or_else(|e| panic!("failed to write {}: {e}", path.display()));
+}